@@ -0,0 +1,125 @@
+//  Running a command over many schematics at once: every schematic in a
+//  directory (and its subdirectories), or every file matching a glob like
+//  `builds/**/*.litematic`. Outputs go into a directory that mirrors the layout
+//  of the inputs.
+//
+//  Schematics are processed in parallel; a failure in one of them doesn't stop
+//  the others, and everything is reported once all of them are done.
+
+use anyhow::{bail, Context, Result};
+use log::debug;
+use rayon::prelude::*;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::progress;
+
+const EXTENSIONS: [&str; 4] = ["litematic", "schem", "nbt", "schematic"];
+const WILDCARDS: [char; 3] = ['*', '?', '['];
+
+// whether `input` names many schematics rather than a single file; a file that
+// exists is never a pattern, even with brackets in its name like `castle [v2]`
+pub(crate) fn is_batch(input: &str) -> bool {
+    let path = Path::new(input);
+    !path.is_file() && (input.contains(WILDCARDS) || path.is_dir())
+}
+
+// the schematics named by `input`, along with the directory their paths are
+// relative to when mirroring them into an output directory
+fn find(input: &str) -> Result<(PathBuf, Vec<PathBuf>)> {
+    let path = Path::new(input);
+    if path.is_dir() {
+        let mut files = vec![];
+        walk(path, &mut files)?;
+        files.sort();
+        return Ok((path.to_path_buf(), files));
+    }
+
+    // everything in the pattern before the first wildcard
+    let base = path
+        .components()
+        .take_while(|c| !c.as_os_str().to_string_lossy().contains(WILDCARDS))
+        .collect();
+    let mut files = vec![];
+    for entry in glob::glob(input).with_context(|| format!("Invalid pattern {}", input))? {
+        let entry = entry?;
+        if entry.is_file() {
+            files.push(entry);
+        }
+    }
+    Ok((base, files))
+}
+
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let entries = fs::read_dir(dir).with_context(|| format!("Can't read {}", dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            walk(&path, files)?;
+        } else if path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| EXTENSIONS.contains(&e))
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Runs `f` on every schematic named by `input`, with the path of the schematic
+/// and the path its output should go to, and prints what `f` returned for each.
+/// Without `output_dir`, outputs go over their inputs.
+pub(crate) fn run<F>(input: &str, output_dir: Option<&str>, f: F) -> Result<()>
+where
+    F: Fn(&str, &str) -> Result<String> + Sync,
+{
+    let (base, files) = find(input)?;
+    if files.is_empty() {
+        bail!("No schematics found in {}", input);
+    }
+    debug!("Processing {} schematics from {}", files.len(), input);
+
+    // the progress of each schematic would get in the way of each other
+    progress::set_in_batch(true);
+    let progress = progress::batch_bar(files.len() as u64, "Processing schematics");
+    let results: Vec<Result<String>> = files
+        .par_iter()
+        .map(|file| {
+            let output = match output_dir {
+                Some(dir) => {
+                    let relative = file.strip_prefix(&base).unwrap_or(file);
+                    let output = Path::new(dir).join(relative);
+                    if let Some(parent) = output.parent() {
+                        fs::create_dir_all(parent)
+                            .with_context(|| format!("Can't create {}", parent.display()))?;
+                    }
+                    output
+                }
+                None => file.clone(),
+            };
+            let result = f(&file.to_string_lossy(), &output.to_string_lossy());
+            progress.inc(1);
+            result
+        })
+        .collect();
+    progress.finish_and_clear();
+    progress::set_in_batch(false);
+
+    let mut failed = 0;
+    for (file, result) in files.iter().zip(results) {
+        match result {
+            Ok(summary) => println!("{}: {}", file.display(), summary),
+            Err(e) => {
+                println!("{}: failed: {:#}", file.display(), e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        bail!("{} of {} schematics failed", failed, files.len());
+    }
+    Ok(())
+}
@@ -0,0 +1,747 @@
+//  Translation of pre-1.13 numeric block ids and data values into modern
+//  block states, for MCEdit schematics.
+//
+//  Only the properties that can be told from the data value are set (e.g.
+//  where stairs are facing); the rest are left at their default. Some blocks
+//  keep part of what they look like in their block entity, like the color of
+//  beds or the type of skulls, which is lost with it; `left_out` tells which
+//  so the reader can say so. The shape of stairs isn't in the data value
+//  either, vanilla works it out from the neighbouring stairs, and so does
+//  `connect_stairs` once a region is read.
+
+use rustmatica::{util::Vec3, BlockState, Region};
+use std::borrow::Cow;
+
+use crate::Direction;
+
+const COLORS: [&str; 16] = [
+    "white",
+    "orange",
+    "magenta",
+    "light_blue",
+    "yellow",
+    "lime",
+    "pink",
+    "gray",
+    "light_gray",
+    "cyan",
+    "purple",
+    "blue",
+    "brown",
+    "green",
+    "red",
+    "black",
+];
+
+const WOODS: [&str; 6] = ["oak", "spruce", "birch", "jungle", "acacia", "dark_oak"];
+
+// slabs in the order of their data values, for stone slabs (ids 43 and 44)
+const STONE_SLABS: [&str; 8] = [
+    "smooth_stone",
+    "sandstone",
+    "petrified_oak",
+    "cobblestone",
+    "brick",
+    "stone_brick",
+    "nether_brick",
+    "quartz",
+];
+
+// rail shapes in the order of their data values
+const RAIL_SHAPES: [&str; 10] = [
+    "north_south",
+    "east_west",
+    "ascending_east",
+    "ascending_west",
+    "ascending_north",
+    "ascending_south",
+    "south_east",
+    "south_west",
+    "north_west",
+    "north_east",
+];
+
+fn color(data: u8) -> &'static str {
+    COLORS[usize::from(data & 15)]
+}
+
+fn wood(data: u8) -> Option<&'static str> {
+    WOODS.get(usize::from(data & 7)).copied()
+}
+
+// the facing used by chests, furnaces, ladders, wall signs, etc.
+fn facing(data: u8) -> &'static str {
+    match data & 7 {
+        3 => "south",
+        4 => "west",
+        5 => "east",
+        _ => "north",
+    }
+}
+
+// the facing of blocks that can face up and down too, like dispensers, pistons
+// and observers
+fn facing_any(data: u8) -> &'static str {
+    ["down", "up", "north", "south", "west", "east"][usize::from(data & 7) % 6]
+}
+
+// the facing used by beds, repeaters, fence gates, pumpkins, etc.
+fn horizontal(data: u8) -> &'static str {
+    ["south", "west", "north", "east"][usize::from(data & 3)]
+}
+
+fn stairs(name: &str, data: u8) -> String {
+    let facing = ["east", "west", "south", "north"][usize::from(data & 3)];
+    let half = if data & 4 == 0 { "bottom" } else { "top" };
+    format!(
+        "minecraft:{}_stairs[facing={},half={},shape=straight]",
+        name, facing, half
+    )
+}
+
+// the facing and half of a stairs block
+fn stairs_props<'b>(blockstate: &'b BlockState) -> Option<(&'b str, &'b str)> {
+    if !blockstate.name.ends_with("_stairs") {
+        return None;
+    }
+    let props = blockstate.properties.as_ref()?;
+    Some((props.get("facing")?.as_ref(), props.get("half")?.as_ref()))
+}
+
+fn opposite(facing: &str) -> &'static str {
+    match facing {
+        "north" => "south",
+        "south" => "north",
+        "east" => "west",
+        _ => "east",
+    }
+}
+
+fn counter_clockwise(facing: &str) -> &'static str {
+    match facing {
+        "north" => "west",
+        "west" => "south",
+        "south" => "east",
+        _ => "north",
+    }
+}
+
+// the shape of the stairs at `pos`, the way vanilla's `StairBlock` works it
+// out: stairs turn into an outer corner when the stairs in front of them face
+// sideways, or an inner corner when the stairs behind them do, unless that
+// would break a row of stairs facing the same way
+fn stairs_shape(region: &Region, pos: Vec3, facing: &str, half: &str) -> &'static str {
+    let neighbour = |towards: &str| {
+        let pos = pos + Direction::from_name(towards).ok()?;
+        if !region.contains(&pos) {
+            return None;
+        }
+        stairs_props(region.get_block(pos))
+    };
+    let can_take_shape =
+        |towards: &str| !matches!(neighbour(towards), Some((f, h)) if f == facing && h == half);
+    let same_axis = |other: &str| other == facing || other == opposite(facing);
+
+    if let Some((front, front_half)) = neighbour(facing) {
+        if front_half == half && !same_axis(front) && can_take_shape(opposite(front)) {
+            return if front == counter_clockwise(facing) {
+                "outer_left"
+            } else {
+                "outer_right"
+            };
+        }
+    }
+    if let Some((back, back_half)) = neighbour(opposite(facing)) {
+        if back_half == half && !same_axis(back) && can_take_shape(back) {
+            return if back == counter_clockwise(facing) {
+                "inner_left"
+            } else {
+                "inner_right"
+            };
+        }
+    }
+    "straight"
+}
+
+/// Gives every stairs block in `region` the shape the game would give it from
+/// the stairs around it
+pub(super) fn connect_stairs(region: &mut Region) {
+    let mut connected = vec![];
+    for (pos, blockstate) in region.blocks() {
+        let Some((facing, half)) = stairs_props(blockstate) else {
+            continue;
+        };
+        let shape = stairs_shape(region, pos, facing, half);
+        if shape != "straight" {
+            let mut blockstate = blockstate.clone();
+            if let Some(props) = &mut blockstate.properties {
+                props.insert(Cow::from("shape"), Cow::from(shape));
+            }
+            connected.push((pos, blockstate));
+        }
+    }
+    for (pos, blockstate) in connected {
+        region.set_block(pos, blockstate);
+    }
+}
+
+fn slab(name: &str, data: u8) -> String {
+    let slabtype = if data & 8 == 0 { "bottom" } else { "top" };
+    format!("minecraft:{}_slab[type={}]", name, slabtype)
+}
+
+fn double_slab(name: &str) -> String {
+    format!("minecraft:{}_slab[type=double]", name)
+}
+
+fn log(name: &str, data: u8) -> String {
+    let axis = match data & 12 {
+        4 => "x",
+        8 => "z",
+        _ => "y",
+    };
+    if data & 12 == 12 {
+        format!("minecraft:{}_wood[axis=y]", name)
+    } else {
+        format!("minecraft:{}_log[axis={}]", name, axis)
+    }
+}
+
+fn door(name: &str, data: u8) -> String {
+    // the upper half only knows about the hinge, the lower half knows the rest
+    if data & 8 != 0 {
+        let hinge = if data & 1 == 0 { "left" } else { "right" };
+        return format!("minecraft:{}_door[half=upper,hinge={}]", name, hinge);
+    }
+    let facing = ["east", "south", "west", "north"][usize::from(data & 3)];
+    let open = data & 4 != 0;
+    format!(
+        "minecraft:{}_door[half=lower,facing={},open={}]",
+        name, facing, open
+    )
+}
+
+fn trapdoor(name: &str, data: u8) -> String {
+    let facing = ["north", "south", "west", "east"][usize::from(data & 3)];
+    let open = data & 4 != 0;
+    let half = if data & 8 == 0 { "bottom" } else { "top" };
+    format!(
+        "minecraft:{}_trapdoor[facing={},open={},half={}]",
+        name, facing, open, half
+    )
+}
+
+fn pillar(name: &str, data: u8) -> String {
+    let axis = match data & 12 {
+        4 => "x",
+        8 => "z",
+        _ => "y",
+    };
+    format!("minecraft:{}[axis={}]", name, axis)
+}
+
+fn rail(name: &str, data: u8) -> String {
+    // powered, detector and activator rails can't turn, and use the top bit
+    // for being powered
+    if name != "rail" {
+        let shape = RAIL_SHAPES[usize::from(data & 7) % 6];
+        let powered = data & 8 != 0;
+        return format!("minecraft:{}[shape={},powered={}]", name, shape, powered);
+    }
+    format!(
+        "minecraft:rail[shape={}]",
+        RAIL_SHAPES
+            .get(usize::from(data))
+            .unwrap_or(&RAIL_SHAPES[0])
+    )
+}
+
+fn piston(name: &str, data: u8) -> String {
+    let extended = data & 8 != 0;
+    format!(
+        "minecraft:{}[facing={},extended={}]",
+        name,
+        facing_any(data),
+        extended
+    )
+}
+
+// where a lever or button is attached, for the data values that put it on a
+// wall
+fn on_wall(data: u8) -> Option<&'static str> {
+    match data & 7 {
+        1 => Some("east"),
+        2 => Some("west"),
+        3 => Some("south"),
+        4 => Some("north"),
+        _ => None,
+    }
+}
+
+fn lever(data: u8) -> String {
+    let (face, facing) = match (data & 7, on_wall(data)) {
+        (_, Some(facing)) => ("wall", facing),
+        (0, _) => ("ceiling", "west"),
+        (5, _) => ("floor", "north"),
+        (6, _) => ("floor", "west"),
+        _ => ("ceiling", "north"),
+    };
+    let powered = data & 8 != 0;
+    format!(
+        "minecraft:lever[face={},facing={},powered={}]",
+        face, facing, powered
+    )
+}
+
+fn button(name: &str, data: u8) -> String {
+    let (face, facing) = match (data & 7, on_wall(data)) {
+        (_, Some(facing)) => ("wall", facing),
+        (5, _) => ("floor", "north"),
+        _ => ("ceiling", "north"),
+    };
+    let powered = data & 8 != 0;
+    format!(
+        "minecraft:{}[face={},facing={},powered={}]",
+        name, face, facing, powered
+    )
+}
+
+fn fence_gate(name: &str, data: u8) -> String {
+    let open = data & 4 != 0;
+    format!(
+        "minecraft:{}_fence_gate[facing={},open={}]",
+        name,
+        horizontal(data),
+        open
+    )
+}
+
+fn simple(name: &str) -> String {
+    format!("minecraft:{}", name)
+}
+
+fn variant(names: &[&str], data: u8) -> Option<String> {
+    names.get(usize::from(data)).map(|name| simple(name))
+}
+
+pub(super) fn blockstate(id: u16, data: u8) -> Option<String> {
+    let blockstate = match id {
+        0 => simple("air"),
+        1 => variant(
+            &[
+                "stone",
+                "granite",
+                "polished_granite",
+                "diorite",
+                "polished_diorite",
+                "andesite",
+                "polished_andesite",
+            ],
+            data,
+        )?,
+        2 => simple("grass_block"),
+        3 => variant(&["dirt", "coarse_dirt", "podzol"], data)?,
+        4 => simple("cobblestone"),
+        5 => format!("minecraft:{}_planks", wood(data)?),
+        6 => format!("minecraft:{}_sapling", wood(data)?),
+        7 => simple("bedrock"),
+        8 | 9 => format!("minecraft:water[level={}]", data & 15),
+        10 | 11 => format!("minecraft:lava[level={}]", data & 15),
+        12 => variant(&["sand", "red_sand"], data)?,
+        13 => simple("gravel"),
+        14 => simple("gold_ore"),
+        15 => simple("iron_ore"),
+        16 => simple("coal_ore"),
+        17 => log(WOODS[usize::from(data & 3)], data),
+        18 => format!("minecraft:{}_leaves", WOODS[usize::from(data & 3)]),
+        19 => variant(&["sponge", "wet_sponge"], data)?,
+        20 => simple("glass"),
+        21 => simple("lapis_ore"),
+        22 => simple("lapis_block"),
+        23 => format!(
+            "minecraft:dispenser[facing={},triggered={}]",
+            facing_any(data),
+            data & 8 != 0
+        ),
+        24 => variant(&["sandstone", "chiseled_sandstone", "cut_sandstone"], data)?,
+        25 => simple("note_block"),
+        26 => {
+            let part = if data & 8 == 0 { "foot" } else { "head" };
+            format!(
+                "minecraft:red_bed[facing={},part={}]",
+                horizontal(data),
+                part
+            )
+        }
+        27 => rail("powered_rail", data),
+        28 => rail("detector_rail", data),
+        29 => piston("sticky_piston", data),
+        30 => simple("cobweb"),
+        31 => variant(&["dead_bush", "grass", "fern"], data)?,
+        32 => simple("dead_bush"),
+        33 => piston("piston", data),
+        34 => {
+            let kind = if data & 8 == 0 { "normal" } else { "sticky" };
+            format!(
+                "minecraft:piston_head[facing={},type={}]",
+                facing_any(data),
+                kind
+            )
+        }
+        35 => format!("minecraft:{}_wool", color(data)),
+        37 => simple("dandelion"),
+        38 => variant(
+            &[
+                "poppy",
+                "blue_orchid",
+                "allium",
+                "azure_bluet",
+                "red_tulip",
+                "orange_tulip",
+                "white_tulip",
+                "pink_tulip",
+                "oxeye_daisy",
+            ],
+            data,
+        )?,
+        39 => simple("brown_mushroom"),
+        40 => simple("red_mushroom"),
+        41 => simple("gold_block"),
+        42 => simple("iron_block"),
+        43 => match data {
+            8 => simple("smooth_stone"),
+            9 => simple("smooth_sandstone"),
+            _ => double_slab(STONE_SLABS.get(usize::from(data & 7))?),
+        },
+        44 => slab(STONE_SLABS.get(usize::from(data & 7))?, data),
+        45 => simple("bricks"),
+        46 => simple("tnt"),
+        47 => simple("bookshelf"),
+        48 => simple("mossy_cobblestone"),
+        49 => simple("obsidian"),
+        50 => match data {
+            1 => simple("wall_torch[facing=east]"),
+            2 => simple("wall_torch[facing=west]"),
+            3 => simple("wall_torch[facing=south]"),
+            4 => simple("wall_torch[facing=north]"),
+            _ => simple("torch"),
+        },
+        51 => simple("fire"),
+        52 => simple("spawner"),
+        53 => stairs("oak", data),
+        54 => format!("minecraft:chest[facing={}]", facing(data)),
+        55 => format!("minecraft:redstone_wire[power={}]", data & 15),
+        56 => simple("diamond_ore"),
+        57 => simple("diamond_block"),
+        58 => simple("crafting_table"),
+        59 => format!("minecraft:wheat[age={}]", data & 7),
+        60 => simple("farmland"),
+        61 => format!("minecraft:furnace[facing={}]", facing(data)),
+        62 => format!("minecraft:furnace[facing={},lit=true]", facing(data)),
+        63 => format!("minecraft:oak_sign[rotation={}]", data & 15),
+        64 => door("oak", data),
+        65 => format!("minecraft:ladder[facing={}]", facing(data)),
+        66 => rail("rail", data),
+        67 => stairs("cobblestone", data),
+        68 => format!("minecraft:oak_wall_sign[facing={}]", facing(data)),
+        69 => lever(data),
+        70 => simple("stone_pressure_plate"),
+        71 => door("iron", data),
+        72 => simple("oak_pressure_plate"),
+        73 => simple("redstone_ore"),
+        74 => simple("redstone_ore[lit=true]"),
+        75 | 76 => {
+            let lit = id == 76;
+            match data {
+                1 => format!("minecraft:redstone_wall_torch[facing=east,lit={}]", lit),
+                2 => format!("minecraft:redstone_wall_torch[facing=west,lit={}]", lit),
+                3 => format!("minecraft:redstone_wall_torch[facing=south,lit={}]", lit),
+                4 => format!("minecraft:redstone_wall_torch[facing=north,lit={}]", lit),
+                _ => format!("minecraft:redstone_torch[lit={}]", lit),
+            }
+        }
+        77 => button("stone_button", data),
+        78 => format!("minecraft:snow[layers={}]", (data & 7) + 1),
+        79 => simple("ice"),
+        80 => simple("snow_block"),
+        81 => simple("cactus"),
+        82 => simple("clay"),
+        83 => simple("sugar_cane"),
+        84 => simple("jukebox"),
+        85 => simple("oak_fence"),
+        86 => format!("minecraft:carved_pumpkin[facing={}]", horizontal(data)),
+        87 => simple("netherrack"),
+        88 => simple("soul_sand"),
+        89 => simple("glowstone"),
+        90 => simple("nether_portal"),
+        91 => format!("minecraft:jack_o_lantern[facing={}]", horizontal(data)),
+        92 => simple("cake"),
+        93 | 94 => format!(
+            "minecraft:repeater[facing={},delay={},powered={}]",
+            horizontal(data),
+            (data >> 2) + 1,
+            id == 94
+        ),
+        95 => format!("minecraft:{}_stained_glass", color(data)),
+        96 => trapdoor("oak", data),
+        97 => variant(
+            &[
+                "infested_stone",
+                "infested_cobblestone",
+                "infested_stone_bricks",
+                "infested_mossy_stone_bricks",
+                "infested_cracked_stone_bricks",
+                "infested_chiseled_stone_bricks",
+            ],
+            data,
+        )?,
+        98 => variant(
+            &[
+                "stone_bricks",
+                "mossy_stone_bricks",
+                "cracked_stone_bricks",
+                "chiseled_stone_bricks",
+            ],
+            data,
+        )?,
+        99 => simple("brown_mushroom_block"),
+        100 => simple("red_mushroom_block"),
+        101 => simple("iron_bars"),
+        102 => simple("glass_pane"),
+        103 => simple("melon"),
+        104 => simple("pumpkin_stem"),
+        105 => simple("melon_stem"),
+        106 => format!(
+            "minecraft:vine[south={},west={},north={},east={}]",
+            data & 1 != 0,
+            data & 2 != 0,
+            data & 4 != 0,
+            data & 8 != 0
+        ),
+        107 => fence_gate("oak", data),
+        108 => stairs("brick", data),
+        109 => stairs("stone_brick", data),
+        110 => simple("mycelium"),
+        111 => simple("lily_pad"),
+        112 => simple("nether_bricks"),
+        113 => simple("nether_brick_fence"),
+        114 => stairs("nether_brick", data),
+        115 => simple("nether_wart"),
+        116 => simple("enchanting_table"),
+        117 => simple("brewing_stand"),
+        118 => simple("cauldron"),
+        119 => simple("end_portal"),
+        120 => format!(
+            "minecraft:end_portal_frame[facing={},eye={}]",
+            horizontal(data),
+            data & 4 != 0
+        ),
+        121 => simple("end_stone"),
+        122 => simple("dragon_egg"),
+        123 => simple("redstone_lamp"),
+        124 => simple("redstone_lamp[lit=true]"),
+        125 => double_slab(wood(data)?),
+        126 => slab(wood(data)?, data),
+        127 => format!(
+            "minecraft:cocoa[facing={},age={}]",
+            horizontal(data),
+            (data >> 2) & 3
+        ),
+        128 => stairs("sandstone", data),
+        129 => simple("emerald_ore"),
+        130 => format!("minecraft:ender_chest[facing={}]", facing(data)),
+        131 => format!(
+            "minecraft:tripwire_hook[facing={},attached={},powered={}]",
+            horizontal(data),
+            data & 4 != 0,
+            data & 8 != 0
+        ),
+        132 => simple("tripwire"),
+        133 => simple("emerald_block"),
+        134 => stairs("spruce", data),
+        135 => stairs("birch", data),
+        136 => stairs("jungle", data),
+        137 => simple("command_block"),
+        138 => simple("beacon"),
+        139 => variant(&["cobblestone_wall", "mossy_cobblestone_wall"], data)?,
+        140 => simple("flower_pot"),
+        141 => simple("carrots"),
+        142 => simple("potatoes"),
+        143 => button("oak_button", data),
+        // the type of skull is in the block entity, and so is the rotation of
+        // those on the floor
+        144 => match data & 7 {
+            2..=5 => format!("minecraft:skeleton_wall_skull[facing={}]", facing(data)),
+            _ => simple("skeleton_skull"),
+        },
+        145 => format!(
+            "minecraft:{}[facing={}]",
+            ["anvil", "chipped_anvil", "damaged_anvil"].get(usize::from(data >> 2))?,
+            horizontal(data)
+        ),
+        146 => format!("minecraft:trapped_chest[facing={}]", facing(data)),
+        147 => simple("light_weighted_pressure_plate"),
+        148 => simple("heavy_weighted_pressure_plate"),
+        149 | 150 => {
+            let mode = if data & 4 == 0 { "compare" } else { "subtract" };
+            format!(
+                "minecraft:comparator[facing={},mode={},powered={}]",
+                horizontal(data),
+                mode,
+                data & 8 != 0
+            )
+        }
+        151 => simple("daylight_detector"),
+        152 => simple("redstone_block"),
+        153 => simple("nether_quartz_ore"),
+        154 => {
+            // hoppers can't face up, those that say so face down
+            let facing = match facing_any(data) {
+                "up" => "down",
+                facing => facing,
+            };
+            format!(
+                "minecraft:hopper[facing={},enabled={}]",
+                facing,
+                data & 8 == 0
+            )
+        }
+        155 => match data {
+            0 => simple("quartz_block"),
+            1 => simple("chiseled_quartz_block"),
+            2 => simple("quartz_pillar[axis=y]"),
+            3 => simple("quartz_pillar[axis=x]"),
+            4 => simple("quartz_pillar[axis=z]"),
+            _ => return None,
+        },
+        156 => stairs("quartz", data),
+        157 => rail("activator_rail", data),
+        158 => format!(
+            "minecraft:dropper[facing={},triggered={}]",
+            facing_any(data),
+            data & 8 != 0
+        ),
+        159 => format!("minecraft:{}_terracotta", color(data)),
+        160 => format!("minecraft:{}_stained_glass_pane", color(data)),
+        161 => format!("minecraft:{}_leaves", WOODS.get(usize::from(data & 3) + 4)?),
+        162 => log(WOODS.get(usize::from(data & 3) + 4)?, data),
+        163 => stairs("acacia", data),
+        164 => stairs("dark_oak", data),
+        165 => simple("slime_block"),
+        166 => simple("barrier"),
+        167 => trapdoor("iron", data),
+        168 => variant(
+            &["prismarine", "prismarine_bricks", "dark_prismarine"],
+            data,
+        )?,
+        169 => simple("sea_lantern"),
+        170 => pillar("hay_block", data),
+        171 => format!("minecraft:{}_carpet", color(data)),
+        172 => simple("terracotta"),
+        173 => simple("coal_block"),
+        174 => simple("packed_ice"),
+        175 => {
+            // the upper half doesn't know which plant it is
+            if data & 8 != 0 {
+                simple("tall_grass[half=upper]")
+            } else {
+                format!(
+                    "minecraft:{}[half=lower]",
+                    [
+                        "sunflower",
+                        "lilac",
+                        "tall_grass",
+                        "large_fern",
+                        "rose_bush",
+                        "peony",
+                    ]
+                    .get(usize::from(data & 7))?
+                )
+            }
+        }
+        176 => format!("minecraft:white_banner[rotation={}]", data & 15),
+        177 => format!("minecraft:white_wall_banner[facing={}]", facing(data)),
+        178 => simple("daylight_detector[inverted=true]"),
+        179 => variant(
+            &[
+                "red_sandstone",
+                "chiseled_red_sandstone",
+                "cut_red_sandstone",
+            ],
+            data,
+        )?,
+        180 => stairs("red_sandstone", data),
+        181 => match data {
+            8 => simple("smooth_red_sandstone"),
+            _ => double_slab("red_sandstone"),
+        },
+        182 => slab("red_sandstone", data),
+        183 => fence_gate("spruce", data),
+        184 => fence_gate("birch", data),
+        185 => fence_gate("jungle", data),
+        186 => fence_gate("dark_oak", data),
+        187 => fence_gate("acacia", data),
+        188 => simple("spruce_fence"),
+        189 => simple("birch_fence"),
+        190 => simple("jungle_fence"),
+        191 => simple("dark_oak_fence"),
+        192 => simple("acacia_fence"),
+        193 => door("spruce", data),
+        194 => door("birch", data),
+        195 => door("jungle", data),
+        196 => door("acacia", data),
+        197 => door("dark_oak", data),
+        198 => format!("minecraft:end_rod[facing={}]", facing_any(data)),
+        199 => simple("chorus_plant"),
+        200 => simple("chorus_flower"),
+        201 => simple("purpur_block"),
+        202 => pillar("purpur_pillar", data),
+        203 => stairs("purpur", data),
+        204 => double_slab("purpur"),
+        205 => slab("purpur", data),
+        206 => simple("end_stone_bricks"),
+        207 => simple("beetroots"),
+        208 => simple("dirt_path"),
+        209 => simple("end_gateway"),
+        210 => simple("repeating_command_block"),
+        211 => simple("chain_command_block"),
+        212 => simple("frosted_ice"),
+        213 => simple("magma_block"),
+        214 => simple("nether_wart_block"),
+        215 => simple("red_nether_bricks"),
+        216 => pillar("bone_block", data),
+        217 => simple("structure_void"),
+        218 => format!(
+            "minecraft:observer[facing={},powered={}]",
+            facing_any(data),
+            data & 8 != 0
+        ),
+        219..=234 => format!(
+            "minecraft:{}_shulker_box[facing={}]",
+            COLORS[usize::from(id - 219)],
+            facing_any(data)
+        ),
+        235..=250 => format!(
+            "minecraft:{}_glazed_terracotta[facing={}]",
+            COLORS[usize::from(id - 235)],
+            horizontal(data)
+        ),
+        251 => format!("minecraft:{}_concrete", color(data)),
+        252 => format!("minecraft:{}_concrete_powder", color(data)),
+        255 => simple("structure_block"),
+        _ => return None,
+    };
+    Some(blockstate)
+}
+
+/// What blocks with the id `id` lose by leaving out their block entity, if
+/// they look different because of it
+pub(super) fn left_out(id: u16) -> Option<&'static str> {
+    match id {
+        26 => Some("the color of beds"),
+        140 => Some("the plants in flower pots"),
+        144 => Some("the type of skulls and the rotation of those on the floor"),
+        176 | 177 => Some("the colors and patterns of banners"),
+        _ => None,
+    }
+}
@@ -0,0 +1,115 @@
+//  Counting the blocks of a .litematic without loading it, for `materials`.
+//  The file is parsed as a stream, so no more than the packed block states of
+//  one region are held in memory at a time.
+//
+//  Each region stores its blocks as indices into its palette, packed into longs
+//  with as few bits as the palette needs (but at least 2), with values spanning
+//  from one long into the next. The light flood's palette unpacks them the same
+//  way, see `palette.rs`.
+
+use anyhow::{bail, Context, Result};
+use fastnbt::stream::{Parser, Value};
+use flate2::read::GzDecoder;
+use std::{collections::HashMap, fs::File, io::BufReader};
+
+#[derive(Default)]
+struct RegionBlocks {
+    palette: Vec<String>,
+    states: Vec<i64>,
+    size: [i32; 3],
+}
+
+pub(super) fn count_blocks(path: &str) -> Result<HashMap<String, usize>> {
+    let file = File::open(path).with_context(|| format!("Can't read {}", path))?;
+    let mut parser = Parser::new(GzDecoder::new(BufReader::new(file)));
+
+    let mut counts = HashMap::new();
+    // the names of the compounds and lists the parser is in, from the root; a
+    // region's tags are at `["", "Regions", <name>]`
+    let mut path: Vec<String> = vec![];
+    let mut region = RegionBlocks::default();
+
+    loop {
+        let in_regions = path.len() >= 3 && path[1] == "Regions";
+        match parser.next()? {
+            Value::Compound(name) | Value::List(name, _, _) => {
+                path.push(name.unwrap_or_default());
+            }
+            Value::CompoundEnd | Value::ListEnd => {
+                if in_regions && path.len() == 3 {
+                    count_region(&std::mem::take(&mut region), &mut counts)?;
+                }
+                path.pop();
+                if path.is_empty() {
+                    break;
+                }
+            }
+            Value::Int(Some(name), v) if in_regions && path.len() == 4 && path[3] == "Size" => {
+                match name.as_str() {
+                    "x" => region.size[0] = v,
+                    "y" => region.size[1] = v,
+                    "z" => region.size[2] = v,
+                    _ => {}
+                }
+            }
+            Value::String(Some(name), v)
+                if in_regions
+                    && path.len() == 5
+                    && path[3] == "BlockStatePalette"
+                    && name == "Name" =>
+            {
+                region.palette.push(v);
+            }
+            Value::LongArray(Some(name), v)
+                if in_regions && path.len() == 3 && name == "BlockStates" =>
+            {
+                region.states = v;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(counts)
+}
+
+fn count_region(region: &RegionBlocks, counts: &mut HashMap<String, usize>) -> Result<()> {
+    // sizes are negative when the region extends the other way from its position
+    let volume: usize = region
+        .size
+        .iter()
+        .map(|s| s.unsigned_abs() as usize)
+        .product();
+    let bits = bits_per_block(region.palette.len());
+    if region.states.len() * 64 < volume * bits {
+        bail!("Block states of a region don't match its size");
+    }
+
+    let mut palette_counts = vec![0; region.palette.len()];
+    for i in 0..volume {
+        let index = packed_index(&region.states, bits, i);
+        *palette_counts
+            .get_mut(index)
+            .with_context(|| format!("Block refers to missing palette index {}", index))? += 1;
+    }
+
+    for (name, count) in region.palette.iter().zip(palette_counts) {
+        *counts.entry(name.clone()).or_default() += count;
+    }
+    Ok(())
+}
+
+/// How many bits each block takes in a region with `palette_len` block states
+pub(crate) fn bits_per_block(palette_len: usize) -> usize {
+    (usize::BITS - (palette_len.max(1) - 1).leading_zeros()).max(2) as usize
+}
+
+/// The palette index of the `i`th block in `states`, packed `bits` to a block
+pub(crate) fn packed_index(states: &[i64], bits: usize, i: usize) -> usize {
+    let start = i * bits;
+    let (word, offset) = (start / 64, start % 64);
+    let mut value = (states[word] as u64) >> offset;
+    if offset + bits > 64 {
+        value |= (states[word + 1] as u64) << (64 - offset);
+    }
+    (value & ((1u64 << bits) - 1)) as usize
+}
@@ -0,0 +1,110 @@
+//  MCEdit schematics (.schematic), the format used by MCEdit and WorldEdit
+//  before 1.13. Blocks are stored as numeric ids and data values, which are
+//  translated into block states by `legacy.rs`. These can only be read.
+//
+//  Block entities and entities are left out: their data predates 1.13 too, and
+//  can't be loaded as it is.
+
+use anyhow::{bail, Context, Result};
+use log::warn;
+use rustmatica::{util::Vec3, BlockState, Litematic, Region};
+use std::{
+    borrow::Cow,
+    collections::{hash_map::Entry, HashMap, HashSet},
+};
+
+use super::{get_byte_array, get_int, get_list, get_string, legacy, Compound};
+use crate::parse_blockstate;
+
+pub(super) fn read(root: &Compound, name: &str) -> Result<Litematic<'static>> {
+    if let Ok(materials) = get_string(root, "Materials") {
+        if materials != "Alpha" {
+            bail!("Can't read {} schematics, only Alpha", materials);
+        }
+    }
+
+    // sizes are unsigned shorts
+    let width = get_int(root, "Width")? as u16 as usize;
+    let height = get_int(root, "Height")? as u16 as usize;
+    let length = get_int(root, "Length")? as u16 as usize;
+
+    let ids = get_byte_array(root, "Blocks")?;
+    let data = get_byte_array(root, "Data")?;
+    // ids over 255 keep their top 4 bits here, two blocks per byte
+    let add_blocks = get_byte_array(root, "AddBlocks").unwrap_or(&[]);
+
+    if ids.len() != width * height * length || data.len() != ids.len() {
+        bail!("Block data doesn't match the size of the schematic");
+    }
+
+    let mut litematic = Litematic::new(Cow::from(name.to_owned()), Cow::from(""), Cow::from(""));
+    let mut region = Region::new(
+        Cow::from(name.to_owned()),
+        Vec3::new(0, 0, 0),
+        Vec3::new(width as i32, height as i32, length as i32),
+    );
+
+    let mut blockstates: HashMap<(u16, u8), BlockState<'static>> = HashMap::new();
+    let mut unknown: HashMap<(u16, u8), usize> = HashMap::new();
+    // what's lost along with the block entities
+    let mut left_out = HashSet::new();
+
+    for (i, (id, data)) in ids.iter().zip(data.iter()).enumerate() {
+        let mut id = u16::from(*id as u8);
+        if let Some(add) = add_blocks.get(i >> 1) {
+            let add = *add as u8;
+            let add = if i & 1 == 0 { add & 0x0f } else { add >> 4 };
+            id |= u16::from(add) << 8;
+        }
+        let data = (*data as u8) & 15;
+
+        let blockstate = match blockstates.entry((id, data)) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let Some(name) = legacy::blockstate(id, data) else {
+                    *unknown.entry((id, data)).or_default() += 1;
+                    continue;
+                };
+                left_out.extend(legacy::left_out(id));
+                entry.insert(
+                    parse_blockstate(&name)
+                        .with_context(|| format!("Bad translation for {}:{}", id, data))?,
+                )
+            }
+        };
+        if blockstate.name == "minecraft:air" {
+            continue;
+        }
+
+        let x = i % width;
+        let z = (i / width) % length;
+        let y = i / (width * length);
+        let pos = Vec3::new(
+            region.min_x() + x as i32,
+            region.min_y() + y as i32,
+            region.min_z() + z as i32,
+        );
+        region.set_block(pos, blockstate.clone());
+    }
+    legacy::connect_stairs(&mut region);
+
+    for ((id, data), count) in unknown {
+        warn!(
+            "Unknown block {}:{} replaced with air in {} places",
+            id, data, count
+        );
+    }
+    for what in left_out {
+        warn!("Leaving out {} with the block entities", what);
+    }
+    for key in ["TileEntities", "Entities"] {
+        if let Ok(list) = get_list(root, key) {
+            if !list.is_empty() {
+                warn!("Leaving out {} {} from {}", list.len(), key, name);
+            }
+        }
+    }
+
+    litematic.regions.push(region);
+    Ok(litematic)
+}
@@ -0,0 +1,394 @@
+//  Schematic formats other than .litematic. Everything in Optimatica works on
+//  a `Litematic`, so other formats are converted to one when reading and from
+//  one when writing.
+
+mod legacy;
+mod litematic;
+mod mcedit;
+mod sponge;
+mod structure;
+
+use anyhow::{bail, Context, Result};
+use fastnbt::Value;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use log::debug;
+use rustmatica::{util::Vec3, Litematic, Region};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    ffi::OsString,
+    fs,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::progress;
+
+pub(crate) use litematic::{bits_per_block, packed_index};
+
+type Compound = HashMap<String, Value>;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Format {
+    Litematic,
+    Sponge,
+    Structure,
+    MCEdit,
+}
+
+impl Format {
+    fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "litematic" => Some(Self::Litematic),
+            "schem" => Some(Self::Sponge),
+            "nbt" => Some(Self::Structure),
+            "schematic" => Some(Self::MCEdit),
+            _ => None,
+        }
+    }
+
+    fn from_contents(root: &Compound) -> Option<Self> {
+        if root.contains_key("Regions") {
+            return Some(Self::Litematic);
+        }
+        if root.contains_key("Blocks") && root.contains_key("Data") {
+            return Some(Self::MCEdit);
+        }
+        // v3 puts everything in a `Schematic` compound
+        if root.contains_key("Schematic") || root.contains_key("BlockData") {
+            return Some(Self::Sponge);
+        }
+        if root.contains_key("palette") || root.contains_key("palettes") {
+            return Some(Self::Structure);
+        }
+        None
+    }
+}
+
+pub(crate) fn read(path: &str) -> Result<Litematic<'static>> {
+    let format = match Format::from_extension(Path::new(path)) {
+        Some(format) => format,
+        None => Format::from_contents(&read_nbt(path)?)
+            .with_context(|| format!("Can't tell the format of {}", path))?,
+    };
+    debug!("Reading {} as {:?}", path, format);
+
+    let progress = progress::spinner(format!("Reading {}", path));
+    let schematic = read_format(path, format);
+    progress.finish_and_clear();
+    schematic
+}
+
+fn read_format(path: &str, format: Format) -> Result<Litematic<'static>> {
+    match format {
+        Format::Litematic => Ok(Litematic::read_file(path)?),
+        Format::Sponge => sponge::read(&read_nbt(path)?, &file_stem(path)),
+        Format::Structure => structure::read(&read_nbt(path)?, &file_stem(path)),
+        Format::MCEdit => mcedit::read(&read_nbt(path)?, &file_stem(path)),
+    }
+}
+
+// how many of each block there are in the schematic at `path`, by name; a
+// .litematic is counted without loading all of it
+pub(crate) fn count_blocks(path: &str) -> Result<HashMap<String, usize>> {
+    if Format::from_extension(Path::new(path)) == Some(Format::Litematic) {
+        let progress = progress::spinner(format!("Counting blocks in {}", path));
+        let counts = litematic::count_blocks(path);
+        progress.finish_and_clear();
+        return counts;
+    }
+
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for region in read(path)?.regions.iter() {
+        for (_, blockstate) in region.blocks() {
+            match counts.get_mut(blockstate.name.as_ref()) {
+                Some(count) => *count += 1,
+                None => {
+                    counts.insert(blockstate.name.to_string(), 1);
+                }
+            }
+        }
+    }
+    Ok(counts)
+}
+
+// returns notes about anything in the schematic that the format of `path`
+// couldn't represent
+pub(crate) fn write(schematic: &Litematic, path: &str) -> Result<Vec<String>> {
+    let format = Format::from_extension(Path::new(path)).unwrap_or(Format::Litematic);
+    debug!("Writing {} as {:?}", path, format);
+
+    // write to a temporary file next to `path` and move it into place, so an
+    // interrupted run never leaves a half-written schematic behind
+    let tmp = temp_path(Path::new(path));
+    let progress = progress::spinner(format!("Writing {}", path));
+    let notes = write_format(schematic, format, &tmp).and_then(|notes| {
+        // the data has to be on disk before the rename, or a crash could leave
+        // an empty or truncated file under the final name; Windows only syncs
+        // files opened for writing
+        fs::OpenOptions::new()
+            .write(true)
+            .open(&tmp)
+            .and_then(|file| file.sync_all())
+            .with_context(|| format!("Can't write {}", path))?;
+        fs::rename(&tmp, path).with_context(|| format!("Can't write {}", path))?;
+        Ok(notes)
+    });
+    progress.finish_and_clear();
+    if notes.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    notes
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+fn write_format(schematic: &Litematic, format: Format, path: &Path) -> Result<Vec<String>> {
+    let mut notes = vec![];
+    match format {
+        Format::Litematic => schematic.write_file(path)?,
+        Format::Sponge | Format::Structure => {
+            // metadata the format has no place for
+            let mut left_out = vec![];
+            if format == Format::Structure {
+                if !schematic.name.is_empty() {
+                    left_out.push("name");
+                }
+                if !schematic.author.is_empty() {
+                    left_out.push("author");
+                }
+            }
+            if !schematic.description.is_empty() {
+                left_out.push("description");
+            }
+            let region = merge_regions(schematic, &left_out, &mut notes)?;
+            let ticks = region.pending_block_ticks.len() + region.pending_fluid_ticks.len();
+            if ticks > 0 {
+                notes.push(format!("{} scheduled ticks were left out", ticks));
+            }
+            if format == Format::Sponge {
+                write_nbt(
+                    path,
+                    &sponge::write(schematic, &region, &mut notes)?,
+                    "Schematic",
+                )?;
+            } else {
+                write_nbt(path, &structure::write(schematic, &region)?, "")?;
+            }
+        }
+        Format::MCEdit => bail!("Can't write MCEdit schematics, they predate 1.13"),
+    }
+    Ok(notes)
+}
+
+// formats that hold a single region get all the regions of the schematic
+// merged into one; `left_out` is the metadata of the schematic that's lost
+// along with the names of the regions
+fn merge_regions<'a>(
+    schematic: &Litematic<'a>,
+    left_out: &[&str],
+    notes: &mut Vec<String>,
+) -> Result<Region<'a>> {
+    if let [region] = &schematic.regions[..] {
+        if !left_out.is_empty() {
+            let verb = if left_out.len() == 1 { "was" } else { "were" };
+            notes.push(format!(
+                "The schematic's {} {} left out",
+                join_words(left_out),
+                verb
+            ));
+        }
+        return Ok(region.clone());
+    }
+    if schematic.regions.is_empty() {
+        bail!("{} has no regions", schematic.name);
+    }
+    let metadata = if left_out.is_empty() {
+        String::new()
+    } else {
+        format!(" and the schematic's {}", join_words(left_out))
+    };
+    notes.push(format!(
+        "{} regions were merged into one, their names{} were left out",
+        schematic.regions.len(),
+        metadata
+    ));
+
+    // block positions are relative to the region's position
+    let corners = |region: &Region| {
+        let p = &region.position;
+        (
+            Vec3::new(
+                p.x + region.min_x(),
+                p.y + region.min_y(),
+                p.z + region.min_z(),
+            ),
+            Vec3::new(
+                p.x + region.max_x(),
+                p.y + region.max_y(),
+                p.z + region.max_z(),
+            ),
+        )
+    };
+    let (mut min_pos, mut max_pos) = corners(&schematic.regions[0]);
+    for region in schematic.regions.iter() {
+        let (a, b) = corners(region);
+        min_pos = Vec3::new(min_pos.x.min(a.x), min_pos.y.min(a.y), min_pos.z.min(a.z));
+        max_pos = Vec3::new(max_pos.x.max(b.x), max_pos.y.max(b.y), max_pos.z.max(b.z));
+    }
+
+    let mut merged = Region::new(
+        Cow::from(schematic.name.to_string()),
+        min_pos,
+        Vec3::new(
+            max_pos.x - min_pos.x + 1,
+            max_pos.y - min_pos.y + 1,
+            max_pos.z - min_pos.z + 1,
+        ),
+    );
+
+    for region in schematic.regions.iter() {
+        // how much a position moves from `region` into `merged`
+        let delta = Vec3::new(
+            region.position.x - min_pos.x + merged.min_x(),
+            region.position.y - min_pos.y + merged.min_y(),
+            region.position.z - min_pos.z + merged.min_z(),
+        );
+        for (pos, blockstate) in region.blocks() {
+            if blockstate.name != "minecraft:air" {
+                merged.set_block(pos + delta, blockstate.clone());
+            }
+        }
+        for tile_entity in region.tile_entities.iter() {
+            let mut tile_entity = tile_entity.clone();
+            for (key, d) in [("x", delta.x), ("y", delta.y), ("z", delta.z)] {
+                let v = get_int(&tile_entity, key)?;
+                tile_entity.insert(key.to_owned(), Value::Int(v + d));
+            }
+            merged.tile_entities.push(tile_entity);
+        }
+        for entity in region.entities.iter() {
+            let mut entity = entity.clone();
+            let [x, y, z] = get_double_list(&entity, "Pos")?;
+            entity.insert(
+                "Pos".to_owned(),
+                Value::List(vec![
+                    Value::Double(x + f64::from(delta.x)),
+                    Value::Double(y + f64::from(delta.y)),
+                    Value::Double(z + f64::from(delta.z)),
+                ]),
+            );
+            merged.entities.push(entity);
+        }
+    }
+
+    Ok(merged)
+}
+
+// e.g. "name, author and description"
+fn join_words(words: &[&str]) -> String {
+    match words {
+        [rest @ .., last] if !rest.is_empty() => format!("{} and {}", rest.join(", "), last),
+        _ => words.join(""),
+    }
+}
+
+// formats that don't store a name use the file name instead
+fn file_stem(path: &str) -> String {
+    Path::new(path)
+        .file_stem()
+        .map_or(String::from("Unnamed"), |stem| {
+            stem.to_string_lossy().into()
+        })
+}
+
+// read an NBT file, gzipped or not
+fn read_nbt(path: &str) -> Result<Compound> {
+    let bytes = fs::read(path).with_context(|| format!("Can't read {}", path))?;
+    let bytes = if bytes.starts_with(&[0x1f, 0x8b]) {
+        let mut decompressed = vec![];
+        GzDecoder::new(&bytes[..]).read_to_end(&mut decompressed)?;
+        decompressed
+    } else {
+        bytes
+    };
+    Ok(fastnbt::from_bytes(&bytes)?)
+}
+
+fn write_nbt(path: &Path, root: &Compound, root_name: &str) -> Result<()> {
+    let bytes = fastnbt::to_bytes_with_opts(root, fastnbt::SerOpts::new().root_name(root_name))?;
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    encoder.write_all(&bytes)?;
+    fs::write(path, encoder.finish()?).with_context(|| format!("Can't write {}", path.display()))
+}
+
+fn get<'v>(compound: &'v Compound, key: &str) -> Result<&'v Value> {
+    compound
+        .get(key)
+        .with_context(|| format!("Missing {} tag", key))
+}
+
+fn get_int(compound: &Compound, key: &str) -> Result<i32> {
+    match get(compound, key)? {
+        Value::Byte(v) => Ok(i32::from(*v)),
+        Value::Short(v) => Ok(i32::from(*v)),
+        Value::Int(v) => Ok(*v),
+        _ => bail!("{} tag should be a number", key),
+    }
+}
+
+fn get_string<'v>(compound: &'v Compound, key: &str) -> Result<&'v str> {
+    match get(compound, key)? {
+        Value::String(v) => Ok(v.as_str()),
+        _ => bail!("{} tag should be a string", key),
+    }
+}
+
+fn get_compound<'v>(compound: &'v Compound, key: &str) -> Result<&'v Compound> {
+    match get(compound, key)? {
+        Value::Compound(v) => Ok(v),
+        _ => bail!("{} tag should be a compound", key),
+    }
+}
+
+fn get_list<'v>(compound: &'v Compound, key: &str) -> Result<&'v [Value]> {
+    match get(compound, key)? {
+        Value::List(v) => Ok(&v[..]),
+        _ => bail!("{} tag should be a list", key),
+    }
+}
+
+fn get_byte_array<'v>(compound: &'v Compound, key: &str) -> Result<&'v [i8]> {
+    match get(compound, key)? {
+        Value::ByteArray(v) => Ok(&v[..]),
+        _ => bail!("{} tag should be a byte array", key),
+    }
+}
+
+fn get_int_array<'v>(compound: &'v Compound, key: &str) -> Result<&'v [i32]> {
+    match get(compound, key)? {
+        Value::IntArray(v) => Ok(&v[..]),
+        _ => bail!("{} tag should be an int array", key),
+    }
+}
+
+// a list of 3 ints, as used for positions and sizes
+fn get_int_list(compound: &Compound, key: &str) -> Result<[i32; 3]> {
+    match get_list(compound, key)? {
+        [Value::Int(x), Value::Int(y), Value::Int(z)] => Ok([*x, *y, *z]),
+        _ => bail!("{} tag should be a list of 3 ints", key),
+    }
+}
+
+// a list of 3 doubles, as used for entity positions
+fn get_double_list(compound: &Compound, key: &str) -> Result<[f64; 3]> {
+    match get_list(compound, key)? {
+        [Value::Double(x), Value::Double(y), Value::Double(z)] => Ok([*x, *y, *z]),
+        _ => bail!("{} tag should be a list of 3 doubles", key),
+    }
+}
@@ -0,0 +1,333 @@
+//  Sponge schematics (.schem), as used by WorldEdit and FAWE. Both v2 and v3
+//  can be read, v2 is written since every version of WorldEdit reads it.
+//
+//  A Sponge schematic holds a single region, its blocks are stored as
+//  varint-encoded palette indices, with x changing fastest, then z, then y.
+
+use anyhow::{bail, Context, Result};
+use fastnbt::{ByteArray, IntArray, Value};
+use rustmatica::{util::Vec3, BlockState, Litematic, Region};
+use std::{borrow::Cow, collections::HashMap};
+
+use super::{
+    get_byte_array, get_compound, get_double_list, get_int, get_int_array, get_list, get_string,
+    Compound,
+};
+use crate::{format_blockstate, parse_blockstate};
+
+pub(super) fn read(root: &Compound, name: &str) -> Result<Litematic<'static>> {
+    let schematic = match root.get("Schematic") {
+        Some(Value::Compound(schematic)) => schematic,
+        _ => root,
+    };
+
+    let version = get_int(schematic, "Version")?;
+    // sizes are unsigned shorts
+    let width = get_int(schematic, "Width")? as u16 as usize;
+    let height = get_int(schematic, "Height")? as u16 as usize;
+    let length = get_int(schematic, "Length")? as u16 as usize;
+
+    let (palette, data) = if version >= 3 {
+        let blocks = get_compound(schematic, "Blocks")?;
+        (
+            get_compound(blocks, "Palette")?,
+            get_byte_array(blocks, "Data")?,
+        )
+    } else {
+        (
+            get_compound(schematic, "Palette")?,
+            get_byte_array(schematic, "BlockData")?,
+        )
+    };
+
+    let mut blockstates = HashMap::new();
+    for (name, index) in palette {
+        let Value::Int(index) = index else {
+            bail!("Palette index for {} should be an int", name);
+        };
+        blockstates.insert(*index as usize, parse_blockstate(name)?);
+    }
+
+    let offset = match get_int_array(schematic, "Offset") {
+        Ok(&[x, y, z]) => Vec3::new(x, y, z),
+        _ => Vec3::new(0, 0, 0),
+    };
+
+    let metadata = get_compound(schematic, "Metadata").ok();
+    let name = metadata
+        .and_then(|m| get_string(m, "Name").ok())
+        .unwrap_or(name)
+        .to_owned();
+    let author = metadata
+        .and_then(|m| get_string(m, "Author").ok())
+        .unwrap_or("")
+        .to_owned();
+
+    let mut litematic = Litematic::new(Cow::from(name.clone()), Cow::from(""), Cow::from(author));
+    if let Ok(data_version) = get_int(schematic, "DataVersion") {
+        litematic.minecraft_data_version = data_version as u32;
+    }
+
+    let mut region = Region::new(
+        Cow::from(name),
+        offset,
+        Vec3::new(width as i32, height as i32, length as i32),
+    );
+    let indices = read_varints(data)?;
+    if indices.len() != width * height * length {
+        bail!("Block data doesn't match the size of the schematic");
+    }
+    for (i, index) in indices.into_iter().enumerate() {
+        let blockstate = blockstates
+            .get(&index)
+            .with_context(|| format!("Block data refers to missing palette index {}", index))?;
+        if blockstate.name == "minecraft:air" {
+            continue;
+        }
+        let x = i % width;
+        let z = (i / width) % length;
+        let y = i / (width * length);
+        let pos = Vec3::new(
+            region.min_x() + x as i32,
+            region.min_y() + y as i32,
+            region.min_z() + z as i32,
+        );
+        region.set_block(pos, blockstate.clone());
+    }
+    read_block_entities(schematic, version, &mut region)?;
+    read_entities(schematic, &mut region)?;
+    litematic.regions.push(region);
+
+    Ok(litematic)
+}
+
+pub(super) fn write(
+    schematic: &Litematic,
+    region: &Region,
+    notes: &mut Vec<String>,
+) -> Result<Compound> {
+    let origin = Vec3::new(region.min_x(), region.min_y(), region.min_z());
+    let width = region.max_x() - region.min_x() + 1;
+    let height = region.max_y() - region.min_y() + 1;
+    let length = region.max_z() - region.min_z() + 1;
+
+    let mut palette: HashMap<String, usize> = HashMap::new();
+    let mut data = vec![];
+    for y in region.y_range() {
+        for z in region.z_range() {
+            for x in region.x_range() {
+                let blockstate: &BlockState = region.get_block(Vec3::new(x, y, z));
+                let next_index = palette.len();
+                let index = *palette
+                    .entry(format_blockstate(blockstate))
+                    .or_insert(next_index);
+                write_varint(&mut data, index);
+            }
+        }
+    }
+
+    let metadata = Compound::from([
+        ("Name".to_owned(), Value::String(schematic.name.to_string())),
+        (
+            "Author".to_owned(),
+            Value::String(schematic.author.to_string()),
+        ),
+    ]);
+
+    Ok(Compound::from([
+        ("Version".to_owned(), Value::Int(2)),
+        (
+            "DataVersion".to_owned(),
+            Value::Int(schematic.minecraft_data_version as i32),
+        ),
+        ("Width".to_owned(), Value::Short(width as i16)),
+        ("Height".to_owned(), Value::Short(height as i16)),
+        ("Length".to_owned(), Value::Short(length as i16)),
+        (
+            "Offset".to_owned(),
+            Value::IntArray(IntArray::new(vec![
+                region.position.x,
+                region.position.y,
+                region.position.z,
+            ])),
+        ),
+        ("Metadata".to_owned(), Value::Compound(metadata)),
+        ("PaletteMax".to_owned(), Value::Int(palette.len() as i32)),
+        (
+            "Palette".to_owned(),
+            Value::Compound(
+                palette
+                    .into_iter()
+                    .map(|(name, index)| (name, Value::Int(index as i32)))
+                    .collect(),
+            ),
+        ),
+        (
+            "BlockData".to_owned(),
+            Value::ByteArray(ByteArray::new(data)),
+        ),
+        (
+            "BlockEntities".to_owned(),
+            Value::List(write_block_entities(region, origin, notes)?),
+        ),
+        (
+            "Entities".to_owned(),
+            Value::List(write_entities(region, origin, notes)?),
+        ),
+    ]))
+}
+
+// v2 keeps the data of block entities and entities next to `Pos` and `Id`,
+// v3 keeps it in a `Data` compound
+fn entity_data(entity: &Compound) -> Compound {
+    let mut data = match get_compound(entity, "Data") {
+        Ok(data) => data.clone(),
+        Err(_) => entity.clone(),
+    };
+    for key in ["Pos", "Id", "Data"] {
+        data.remove(key);
+    }
+    data
+}
+
+fn read_block_entities(schematic: &Compound, version: i32, region: &mut Region) -> Result<()> {
+    let block_entities = if version >= 3 {
+        get_list(get_compound(schematic, "Blocks")?, "BlockEntities")
+    } else {
+        get_list(schematic, "BlockEntities")
+    };
+    let Ok(block_entities) = block_entities else {
+        return Ok(());
+    };
+
+    for block_entity in block_entities {
+        let Value::Compound(block_entity) = block_entity else {
+            bail!("Block entities should be compounds");
+        };
+        let &[x, y, z] = get_int_array(block_entity, "Pos")? else {
+            bail!("Block entity position should have 3 coordinates");
+        };
+        let mut tile_entity = entity_data(block_entity);
+        tile_entity.insert(
+            "id".to_owned(),
+            Value::String(get_string(block_entity, "Id")?.to_owned()),
+        );
+        tile_entity.insert("x".to_owned(), Value::Int(region.min_x() + x));
+        tile_entity.insert("y".to_owned(), Value::Int(region.min_y() + y));
+        tile_entity.insert("z".to_owned(), Value::Int(region.min_z() + z));
+        region.tile_entities.push(tile_entity);
+    }
+    Ok(())
+}
+
+fn read_entities(schematic: &Compound, region: &mut Region) -> Result<()> {
+    let Ok(entities) = get_list(schematic, "Entities") else {
+        return Ok(());
+    };
+
+    for entity in entities {
+        let Value::Compound(entity) = entity else {
+            bail!("Entities should be compounds");
+        };
+        let [x, y, z] = get_double_list(entity, "Pos")?;
+        let mut data = entity_data(entity);
+        data.insert(
+            "id".to_owned(),
+            Value::String(get_string(entity, "Id")?.to_owned()),
+        );
+        data.insert(
+            "Pos".to_owned(),
+            Value::List(vec![
+                Value::Double(x + f64::from(region.min_x())),
+                Value::Double(y + f64::from(region.min_y())),
+                Value::Double(z + f64::from(region.min_z())),
+            ]),
+        );
+        region.entities.push(data);
+    }
+    Ok(())
+}
+
+fn write_block_entities(
+    region: &Region,
+    origin: Vec3,
+    notes: &mut Vec<String>,
+) -> Result<Vec<Value>> {
+    let mut block_entities = vec![];
+    for tile_entity in region.tile_entities.iter() {
+        let Ok(id) = get_string(tile_entity, "id") else {
+            notes.push("A block entity without an id was left out".to_owned());
+            continue;
+        };
+        let pos = vec![
+            get_int(tile_entity, "x")? - origin.x,
+            get_int(tile_entity, "y")? - origin.y,
+            get_int(tile_entity, "z")? - origin.z,
+        ];
+        let mut block_entity = tile_entity.clone();
+        for key in ["x", "y", "z", "id"] {
+            block_entity.remove(key);
+        }
+        block_entity.insert("Id".to_owned(), Value::String(id.to_owned()));
+        block_entity.insert("Pos".to_owned(), Value::IntArray(IntArray::new(pos)));
+        block_entities.push(Value::Compound(block_entity));
+    }
+    Ok(block_entities)
+}
+
+fn write_entities(region: &Region, origin: Vec3, notes: &mut Vec<String>) -> Result<Vec<Value>> {
+    let mut entities = vec![];
+    for entity in region.entities.iter() {
+        let Ok(id) = get_string(entity, "id") else {
+            notes.push("An entity without an id was left out".to_owned());
+            continue;
+        };
+        let [x, y, z] = get_double_list(entity, "Pos")?;
+        let mut entity = entity.clone();
+        entity.remove("id");
+        entity.insert("Id".to_owned(), Value::String(id.to_owned()));
+        entity.insert(
+            "Pos".to_owned(),
+            Value::List(vec![
+                Value::Double(x - f64::from(origin.x)),
+                Value::Double(y - f64::from(origin.y)),
+                Value::Double(z - f64::from(origin.z)),
+            ]),
+        );
+        entities.push(Value::Compound(entity));
+    }
+    Ok(entities)
+}
+
+fn read_varints(data: &[i8]) -> Result<Vec<usize>> {
+    let mut values = vec![];
+    let mut value = 0;
+    let mut shift = 0;
+    for byte in data.iter().map(|b| *b as u8) {
+        value |= usize::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            values.push(value);
+            value = 0;
+            shift = 0;
+        } else {
+            shift += 7;
+            if shift > 28 {
+                bail!("Varint too long in block data");
+            }
+        }
+    }
+    Ok(values)
+}
+
+fn write_varint(data: &mut Vec<i8>, value: usize) {
+    let mut value = value;
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            data.push(byte as i8);
+            break;
+        }
+        data.push((byte | 0x80) as i8);
+    }
+}
@@ -0,0 +1,225 @@
+//  Vanilla structure files (.nbt), as saved by structure blocks and used by
+//  jigsaw pieces and data packs.
+//
+//  A structure holds a single region, as a palette plus a list of blocks that
+//  refer to it; positions that are not in the list are structure voids, which
+//  are read as air. Block entity data goes with each block, in its `nbt` tag.
+
+use anyhow::{bail, Context, Result};
+use fastnbt::Value;
+use rustmatica::{util::Vec3, BlockState, Litematic, Region};
+use std::{borrow::Cow, collections::HashMap};
+
+use super::{get_compound, get_double_list, get_int, get_int_list, get_list, get_string, Compound};
+use crate::format_blockstate;
+
+fn read_palette_entry(entry: &Value) -> Result<BlockState<'static>> {
+    let Value::Compound(entry) = entry else {
+        bail!("Palette entries should be compounds");
+    };
+    let name = get_string(entry, "Name")?;
+    let properties = match get_compound(entry, "Properties") {
+        Ok(properties) => Some(
+            properties
+                .iter()
+                .map(|(key, value)| match value {
+                    Value::String(value) => Ok((Cow::from(key.clone()), Cow::from(value.clone()))),
+                    _ => bail!("Property {} of {} should be a string", key, name),
+                })
+                .collect::<Result<HashMap<_, _>>>()?,
+        ),
+        Err(_) => None,
+    };
+    Ok(BlockState {
+        name: Cow::from(name.to_owned()),
+        properties,
+    })
+}
+
+pub(super) fn read(root: &Compound, name: &str) -> Result<Litematic<'static>> {
+    // structures with random variants, like shipwrecks, have several palettes;
+    // only the first one is used
+    let palette = match get_list(root, "palette") {
+        Ok(palette) => palette,
+        Err(_) => match get_list(root, "palettes")?.first() {
+            Some(Value::List(palette)) => palette,
+            _ => bail!("Structure has no palette"),
+        },
+    };
+    let blockstates = palette
+        .iter()
+        .map(read_palette_entry)
+        .collect::<Result<Vec<_>>>()?;
+
+    let [width, height, length] = get_int_list(root, "size")?;
+
+    let mut litematic = Litematic::new(Cow::from(name.to_owned()), Cow::from(""), Cow::from(""));
+    if let Ok(data_version) = get_int(root, "DataVersion") {
+        litematic.minecraft_data_version = data_version as u32;
+    }
+
+    let mut region = Region::new(
+        Cow::from(name.to_owned()),
+        Vec3::new(0, 0, 0),
+        Vec3::new(width, height, length),
+    );
+    let origin = Vec3::new(region.min_x(), region.min_y(), region.min_z());
+
+    for block in get_list(root, "blocks")? {
+        let Value::Compound(block) = block else {
+            bail!("Blocks should be compounds");
+        };
+        let [x, y, z] = get_int_list(block, "pos")?;
+        let pos = origin + Vec3::new(x, y, z);
+        if !region.contains(&pos) {
+            bail!(
+                "Block at {},{},{} is outside the structure's size {}x{}x{}",
+                x,
+                y,
+                z,
+                width,
+                height,
+                length
+            );
+        }
+        let state = get_int(block, "state")? as usize;
+        let blockstate = blockstates
+            .get(state)
+            .with_context(|| format!("Block refers to missing palette index {}", state))?;
+        if blockstate.name != "minecraft:air" {
+            region.set_block(pos, blockstate.clone());
+        }
+
+        if let Ok(nbt) = get_compound(block, "nbt") {
+            let mut tile_entity = nbt.clone();
+            tile_entity.insert("x".to_owned(), Value::Int(pos.x));
+            tile_entity.insert("y".to_owned(), Value::Int(pos.y));
+            tile_entity.insert("z".to_owned(), Value::Int(pos.z));
+            region.tile_entities.push(tile_entity);
+        }
+    }
+
+    if let Ok(entities) = get_list(root, "entities") {
+        for entity in entities {
+            let Value::Compound(entity) = entity else {
+                bail!("Entities should be compounds");
+            };
+            let [x, y, z] = get_double_list(entity, "pos")?;
+            let mut nbt = get_compound(entity, "nbt")?.clone();
+            nbt.insert(
+                "Pos".to_owned(),
+                Value::List(vec![
+                    Value::Double(x + f64::from(origin.x)),
+                    Value::Double(y + f64::from(origin.y)),
+                    Value::Double(z + f64::from(origin.z)),
+                ]),
+            );
+            region.entities.push(nbt);
+        }
+    }
+
+    litematic.regions.push(region);
+    Ok(litematic)
+}
+
+fn int_list(values: [i32; 3]) -> Value {
+    Value::List(values.map(Value::Int).to_vec())
+}
+
+fn palette_entry(blockstate: &BlockState) -> Value {
+    let mut entry = Compound::from([(
+        "Name".to_owned(),
+        Value::String(blockstate.name.to_string()),
+    )]);
+    if let Some(properties) = &blockstate.properties {
+        entry.insert(
+            "Properties".to_owned(),
+            Value::Compound(
+                properties
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), Value::String(value.to_string())))
+                    .collect(),
+            ),
+        );
+    }
+    Value::Compound(entry)
+}
+
+pub(super) fn write(schematic: &Litematic, region: &Region) -> Result<Compound> {
+    let origin = Vec3::new(region.min_x(), region.min_y(), region.min_z());
+
+    let mut tile_entities = HashMap::new();
+    for tile_entity in region.tile_entities.iter() {
+        let pos = Vec3::new(
+            get_int(tile_entity, "x")?,
+            get_int(tile_entity, "y")?,
+            get_int(tile_entity, "z")?,
+        );
+        let mut nbt = tile_entity.clone();
+        for key in ["x", "y", "z"] {
+            nbt.remove(key);
+        }
+        tile_entities.insert(pos, nbt);
+    }
+
+    let mut indices: HashMap<String, i32> = HashMap::new();
+    let mut palette = vec![];
+    let mut blocks = vec![];
+    for (pos, blockstate) in region.blocks() {
+        let next_index = palette.len() as i32;
+        let state = *indices
+            .entry(format_blockstate(blockstate))
+            .or_insert_with(|| {
+                palette.push(palette_entry(blockstate));
+                next_index
+            });
+
+        let mut block = Compound::from([
+            (
+                "pos".to_owned(),
+                int_list([pos.x - origin.x, pos.y - origin.y, pos.z - origin.z]),
+            ),
+            ("state".to_owned(), Value::Int(state)),
+        ]);
+        if let Some(nbt) = tile_entities.remove(&pos) {
+            block.insert("nbt".to_owned(), Value::Compound(nbt));
+        }
+        blocks.push(Value::Compound(block));
+    }
+
+    let mut entities = vec![];
+    for entity in region.entities.iter() {
+        let [x, y, z] = get_double_list(entity, "Pos")?;
+        let [x, y, z] = [
+            x - f64::from(origin.x),
+            y - f64::from(origin.y),
+            z - f64::from(origin.z),
+        ];
+        entities.push(Value::Compound(Compound::from([
+            (
+                "pos".to_owned(),
+                Value::List(vec![Value::Double(x), Value::Double(y), Value::Double(z)]),
+            ),
+            (
+                "blockPos".to_owned(),
+                int_list([x.floor() as i32, y.floor() as i32, z.floor() as i32]),
+            ),
+            ("nbt".to_owned(), Value::Compound(entity.clone())),
+        ])));
+    }
+
+    let width = region.max_x() - region.min_x() + 1;
+    let height = region.max_y() - region.min_y() + 1;
+    let length = region.max_z() - region.min_z() + 1;
+
+    Ok(Compound::from([
+        (
+            "DataVersion".to_owned(),
+            Value::Int(schematic.minecraft_data_version as i32),
+        ),
+        ("size".to_owned(), int_list([width, height, length])),
+        ("palette".to_owned(), Value::List(palette)),
+        ("blocks".to_owned(), Value::List(blocks)),
+        ("entities".to_owned(), Value::List(entities)),
+    ]))
+}
@@ -4,15 +4,30 @@ use cached::UnboundCache;
 use counter::Counter;
 use itertools::iproduct;
 use lazy_static::lazy_static;
-use log::debug;
+use log::{debug, trace, warn};
+use palette::{FaceMasks, PackedBlocks, Palette, Shapes};
+use rayon::prelude::*;
 use rustmatica::{util::Vec3, BlockState, Litematic, Region};
+mod batch;
+mod formats;
+mod palette;
+mod progress;
+mod sections;
+mod sight;
+#[cfg(test)]
+mod tests;
+mod walk;
+
 use std::{
     borrow::Cow,
+    cmp::Reverse,
     collections::{HashMap, HashSet, VecDeque},
     env,
     error::Error,
+    fmt::Write,
+    fs,
     ops::Add,
-    path::Path,
+    time::{Instant, SystemTime, UNIX_EPOCH},
 };
 
 #[derive(Hash, PartialEq, Eq, Clone, Debug, Copy)]
@@ -123,76 +138,282 @@ lazy_static! {
     );
 }
 
-fn materials(filename: &str) -> Result<(), Box<dyn Error>> {
-    debug!("Reading schematic {}... ", filename);
-    let schematic = Litematic::read_file(filename)?;
+fn materials(filename: &str) -> Result<String> {
+    debug!("Counting blocks in {}... ", filename);
+    let counts = formats::count_blocks(filename)?;
     debug!("done.");
 
-    // count blocks in first region
-    let mut counter = Counter::new();
-    for region in schematic.regions.iter() {
-        for (_, blockstate) in region.blocks() {
-            if blockstate.name == "minecraft:air" {
-                continue;
+    let mut counter: Counter<String, usize> = Counter::new();
+    for (name, count) in counts {
+        if name == "minecraft:air" {
+            continue;
+        }
+        if name.ends_with("_wall_sign") {
+            counter[&name.replace("_wall_sign", "_sign")] += count;
+        } else {
+            counter[&name] += count;
+        }
+    }
+
+    // sort in reverse
+    let mut ml: Vec<(&String, &usize)> = counter.iter().collect();
+    ml.sort_by_key(|(_, v)| Reverse(**v));
+    let mut list = String::new();
+    for (k, v) in ml {
+        writeln!(list, "{} {}", k, v)?;
+    }
+
+    Ok(list)
+}
+
+/// What to put in place of the blocks the optimizer decides are hidden
+enum Filler {
+    Air,
+    Block(BlockState<'static>),
+    /// the most used full block in the schematic, so it adds nothing new to
+    /// the material list
+    Cheapest,
+}
+
+impl Filler {
+    fn resolve(&self, schematic: &Litematic) -> Result<BlockState<'static>> {
+        match self {
+            Filler::Air => Ok(BlockState {
+                name: Cow::from("minecraft:air"),
+                properties: None,
+            }),
+            Filler::Block(blockstate) => Ok(blockstate.clone()),
+            Filler::Cheapest => {
+                let mut counter: Counter<String> = Counter::new();
+                for region in schematic.regions.iter() {
+                    for (_, blockstate) in region.blocks() {
+                        if SOLID_BLOCKS.contains(&blockstate.name) {
+                            counter[&blockstate.name.to_string()] += 1;
+                        }
+                    }
+                }
+                let Some((name, _)) = counter.most_common().into_iter().next() else {
+                    bail!("No full block found in the schematic to fill with");
+                };
+                Ok(BlockState {
+                    name: Cow::from(name),
+                    properties: None,
+                })
             }
-            if blockstate.name.ends_with("_wall_sign") {
-                counter[&Cow::from(blockstate.name.replace("_wall_sign", "_sign"))] += 1;
-            } else {
-                counter[&blockstate.name] += 1;
+        }
+    }
+}
+
+// parse a block state the way it's written in commands,
+// e.g. `minecraft:oak_slab[type=top]`
+fn parse_blockstate(s: &str) -> Result<BlockState<'static>> {
+    let (name, properties) = match s.split_once('[') {
+        Some((name, rest)) => {
+            let Some(rest) = rest.strip_suffix(']') else {
+                bail!("Missing closing ']' in block state {}", s);
+            };
+            let mut properties = HashMap::new();
+            for prop in rest.split(',').filter(|p| !p.is_empty()) {
+                let Some((key, value)) = prop.split_once('=') else {
+                    bail!("Invalid property {} in block state {}", prop, s);
+                };
+                properties.insert(
+                    Cow::from(key.trim().to_owned()),
+                    Cow::from(value.trim().to_owned()),
+                );
             }
+            (name, Some(properties))
         }
+        None => (s, None),
+    };
+    let name = if name.contains(':') {
+        name.to_owned()
+    } else {
+        format!("minecraft:{}", name)
+    };
+    Ok(BlockState {
+        name: Cow::from(name),
+        properties,
+    })
+}
+
+// the inverse of `parse_blockstate`
+fn format_blockstate(blockstate: &BlockState) -> String {
+    let Some(properties) = &blockstate.properties else {
+        return blockstate.name.to_string();
+    };
+    if properties.is_empty() {
+        return blockstate.name.to_string();
     }
+    let mut properties: Vec<String> = properties
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect();
+    properties.sort();
+    format!("{}[{}]", blockstate.name, properties.join(","))
+}
 
-    println!("====== materials =======");
-    // sort in reverse, print
-    let mut ml: Vec<(&Cow<str>, &i32)> = counter.iter().collect();
-    ml.sort_by_key(|(_, v)| -v.to_owned());
-    for (k, v) in ml {
-        println!("{} {}", k, v);
+// the position of a block entity or a scheduled tick
+fn block_data_pos(data: &HashMap<String, fastnbt::Value>) -> Option<Vec3> {
+    let coord = |key: &str| match data.get(key) {
+        Some(fastnbt::Value::Int(v)) => Some(*v),
+        _ => None,
+    };
+    Some(Vec3::new(coord("x")?, coord("y")?, coord("z")?))
+}
+
+// when a block is replaced, its block entity (chest contents, sign text, etc.)
+// and scheduled ticks don't belong to the new block, so they're dropped;
+// `replaced` tells whether the block at a position is going to be, and has to
+// be called before the blocks are replaced
+fn drop_block_data(region: &mut Region, replaced: impl Fn(&Region, &Vec3) -> bool) {
+    let mut tile_entities = std::mem::take(&mut region.tile_entities);
+    let mut block_ticks = std::mem::take(&mut region.pending_block_ticks);
+    let mut fluid_ticks = std::mem::take(&mut region.pending_fluid_ticks);
+    let keep = |data: &HashMap<String, fastnbt::Value>| {
+        block_data_pos(data).is_none_or(|pos| !replaced(region, &pos))
+    };
+    tile_entities.retain(keep);
+    block_ticks.retain(keep);
+    fluid_ticks.retain(keep);
+    region.tile_entities = tile_entities;
+    region.pending_block_ticks = block_ticks;
+    region.pending_fluid_ticks = fluid_ticks;
+}
+
+/// Metadata to set on the output schematic instead of the input's
+#[derive(Default)]
+struct Metadata {
+    name: Option<String>,
+    author: Option<String>,
+    description: Option<String>,
+}
+
+impl Metadata {
+    // handle `arg` if it's one of the metadata options
+    fn parse_arg(&mut self, arg: &str, args: &mut impl Iterator<Item = String>) -> Result<bool> {
+        let field = match arg {
+            "--name" => &mut self.name,
+            "--author" => &mut self.author,
+            "--description" => &mut self.description,
+            _ => return Ok(false),
+        };
+        *field = Some(
+            args.next()
+                .with_context(|| format!("{} requires a value", arg))?,
+        );
+        Ok(true)
     }
 
-    Ok(())
+    // turn the input schematic into the output one: everything but the
+    // regions is kept, and the modification time is updated
+    fn apply(&self, schematic: &mut Litematic) {
+        if let Some(name) = &self.name {
+            schematic.name = Cow::from(name.clone());
+        }
+        if let Some(author) = &self.author {
+            schematic.author = Cow::from(author.clone());
+        }
+        if let Some(description) = &self.description {
+            schematic.description = Cow::from(description.clone());
+        }
+        schematic.time_modified = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as i64);
+    }
 }
 
-fn replace(input: &str, output: &str) -> Result<(), Box<dyn Error>> {
-    debug!("Reading schematic {}... ", input);
-    let mut schematic = Litematic::read_file(input)?;
-    debug!("done.");
+/// `--in-place` writes the output over the input, after backing it up
+#[derive(Default)]
+struct InPlace {
+    enabled: bool,
+    // name the backup after the time it was made, instead of `<input>.bak`
+    timestamped: bool,
+}
 
-    let mut output_schematic = Litematic::new(
-        Path::new(output)
-            .file_name()
-            .context("filename required")?
-            .to_string_lossy()
-            .replace(".litematic", "")
-            .into(),
-        schematic.description,
-        schematic.author,
-    );
+impl InPlace {
+    // handle `arg` if it's one of the in-place options
+    fn parse_arg(&mut self, arg: &str) -> bool {
+        match arg {
+            "--in-place" => self.enabled = true,
+            "--timestamped-backup" => self.timestamped = true,
+            _ => return false,
+        }
+        true
+    }
 
-    // copy the region to the output schema
-    for region in schematic.regions.iter_mut() {
-        let output_region = region.clone();
-        output_schematic.regions.push(output_region);
-        let Some(output_region) = output_schematic.regions.last_mut() else {
-            continue;
+    // pick the input and output paths out of the positional arguments
+    fn paths<'p>(&self, command: &str, positional: &'p [String]) -> Result<(&'p str, &'p str)> {
+        if self.timestamped && !self.enabled {
+            bail!("--timestamped-backup requires --in-place");
+        }
+        match (positional, self.enabled) {
+            ([input], true) => Ok((input.as_str(), input.as_str())),
+            ([input, output], false) => Ok((input.as_str(), output.as_str())),
+            _ => bail!(
+                "usage: optimatica {} <input> (<output> | --in-place) [options]",
+                command
+            ),
+        }
+    }
+
+    // back up `input` if it's going to be overwritten
+    fn prepare(&self, input: &str) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let backup = if self.timestamped {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs());
+            format!("{}.{}.bak", input, now)
+        } else {
+            format!("{}.bak", input)
         };
+        fs::copy(input, &backup)
+            .with_context(|| format!("Can't back up {} to {}", input, backup))?;
+        debug!("Backed up {} to {}", input, backup);
+        Ok(())
+    }
+}
 
-        for (pos, blockstate) in region.blocks() {
-            if blockstate.name == "minecraft:lime_wool" {
-                output_region.set_block(
-                    pos,
-                    BlockState {
-                        name: Cow::from("minecraft:air"),
-                        properties: None,
-                    },
-                );
-            }
+// returns how many blocks were replaced
+fn replace(input: &str, output: &str, metadata: &Metadata) -> Result<usize> {
+    debug!("Reading schematic {}... ", input);
+    let mut output_schematic = formats::read(input)?;
+    debug!("done.");
+
+    let regions = std::mem::take(&mut output_schematic.regions);
+    metadata.apply(&mut output_schematic);
+
+    let mut count = 0;
+    // edit each region in place and move it to the output schema
+    for mut region in regions {
+        drop_block_data(&mut region, |region, pos| {
+            region.contains(pos) && region.get_block(*pos).name == "minecraft:lime_wool"
+        });
+        let replaced: Vec<Vec3> = region
+            .blocks()
+            .filter(|(_, blockstate)| blockstate.name == "minecraft:lime_wool")
+            .map(|(pos, _)| pos)
+            .collect();
+        for pos in replaced.iter() {
+            region.set_block(
+                *pos,
+                BlockState {
+                    name: Cow::from("minecraft:air"),
+                    properties: None,
+                },
+            );
         }
+        count += replaced.len();
+        output_schematic.regions.push(region);
     }
 
-    output_schematic.write_file(output)?;
-    Ok(())
+    for note in formats::write(&output_schematic, output)? {
+        warn!("{}", note);
+    }
+    Ok(count)
 }
 
 // divide a block shape into 8 sub-blocks
@@ -406,40 +627,35 @@ impl BlockShape {
 //    case, `next` is visible, but it shouldn't be moved to, otherwise
 //    the BFS would "break through the walls".
 
+// the light flood works both out from `FaceMasks`, precomputed for each block
+// state in the region; `can_move` is left to check shapes against
+#[cfg(test)]
 fn can_move(from: &BlockState, to: &BlockState, dir: &Direction) -> bool {
-    let from_shape = BlockShape::from(from);
-    let to_shape = BlockShape::from(to);
-
-    let from_bits = side(*dir)
-        .into_iter()
-        .map(|c| from_shape.corners[c.x][c.y][c.z]);
-
-    let to_bits = side(dir.opposite())
-        .into_iter()
-        .map(|c| to_shape.corners[c.x][c.y][c.z]);
-
-    if from_bits.zip(to_bits).all(|(a, b)| a || b) {
-        return false;
-    }
-
-    true
+    let from_masks = FaceMasks::from(&BlockShape::from(from));
+    let to_masks = FaceMasks::from(&BlockShape::from(to));
+    from_masks.can_move(&to_masks, *dir)
 }
 
 fn can_see(from: &BlockState, dir: &Direction) -> bool {
-    let from_shape = BlockShape::from(from);
-
-    if side(*dir)
-        .into_iter()
-        .map(|c| from_shape.corners[c.x][c.y][c.z])
-        .all(|x| x)
-    {
-        return false;
-    }
+    FaceMasks::from(&BlockShape::from(from)).can_see(*dir)
+}
 
-    true
+// all the positions in the 1-block buffer around the region
+fn outside_shell(region: &Region, open_faces: &[Direction]) -> Vec<Vec3> {
+    iproduct!(
+        region.min_x() - 1..=region.max_x() + 1,
+        region.min_y() - 1..=region.max_y() + 1,
+        region.min_z() - 1..=region.max_z() + 1
+    )
+    .map(|(x, y, z)| Vec3::new(x, y, z))
+    .filter(|pos| is_just_outside(pos, region, open_faces))
+    .collect()
 }
 
-fn is_just_outside(pos: &Vec3, region: &Region) -> bool {
+// `open_faces` are the faces of the bounding box the flood is allowed to go
+// around; e.g. without `Direction::Down` nothing under the build is reachable,
+// as if it was sitting on the ground
+fn is_just_outside(pos: &Vec3, region: &Region, open_faces: &[Direction]) -> bool {
     if region.contains(pos) {
         return false;
     }
@@ -452,6 +668,20 @@ fn is_just_outside(pos: &Vec3, region: &Region) -> bool {
     if !(region.min_z() - 1..=region.max_z() + 1).contains(&pos.z) {
         return false;
     }
+    let faces = [
+        (Direction::Down, pos.y < region.min_y()),
+        (Direction::Up, pos.y > region.max_y()),
+        (Direction::North, pos.z < region.min_z()),
+        (Direction::South, pos.z > region.max_z()),
+        (Direction::West, pos.x < region.min_x()),
+        (Direction::East, pos.x > region.max_x()),
+    ];
+    if faces
+        .iter()
+        .any(|(dir, on_face)| *on_face && !open_faces.contains(dir))
+    {
+        return false;
+    }
     true
 }
 
@@ -460,132 +690,357 @@ struct Node {
     gen: usize,
 }
 
-struct PositionTracker<'a> {
-    positions: Vec<bool>,
-    // x: RangeInclusive<i32>,
-    // y: RangeInclusive<i32>,
-    // z: RangeInclusive<i32>,
-    region: Region<'a>,
+// what the light flood finds looking from one position into the next
+struct Step {
+    to: Vec3,
+    dir: Direction,
+    // `to` is in the buffer around the region
+    outside: bool,
+    to_air: bool,
+    // `to` is a block that can be seen from where the flood is
+    seen: bool,
+    movable: bool,
 }
 
-impl<'a> PositionTracker<'a> {
-    fn new(region: &'a Region<'a>) -> Self {
-        // let xr = region.x_range();
-        // let yr = region.y_range();
-        // let zr = region.z_range();
+impl Step {
+    fn new(
+        region: &Region,
+        palette: &Palette,
+        current_block: u32,
+        pos: Vec3,
+        dir: Direction,
+        open_faces: &[Direction],
+    ) -> Option<Self> {
+        let to = pos + dir;
+        if is_just_outside(&to, region, open_faces) {
+            return Some(Self {
+                to,
+                dir,
+                outside: true,
+                to_air: true,
+                seen: false,
+                movable: true,
+            });
+        }
+        if !region.contains(&to) {
+            return None;
+        }
+        let next_block = palette.get(region, &to);
+        let to_air = palette.is_air(next_block);
+        let current_masks = palette.masks(current_block);
+        Some(Self {
+            to,
+            dir,
+            outside: false,
+            to_air,
+            seen: current_masks.can_see(dir) && !to_air,
+            movable: current_masks.can_move(palette.masks(next_block), dir),
+        })
+    }
+}
 
-        let r = Region::new(
-            Cow::from(""),
-            Vec3::new(region.min_x() - 1, region.min_y() - 1, region.min_z() - 1),
-            Vec3::new(region.max_x() + 1, region.max_y() + 1, region.max_z() + 1),
-        );
+// a region plus the 1-block buffer around it, with a place for each position
+// in a flat array
+#[derive(Clone, Copy)]
+struct PaddedBounds {
+    min: Vec3,
+    max: Vec3,
+}
 
-        dbg!(region.min_x(), region.max_x());
-        dbg!(r.x_range(), r.min_x(), r.max_x());
-        dbg!(r.y_range());
-        dbg!(r.z_range());
-        let sx = (r.max_x() - r.min_x() + 1) as usize;
-        let sy = (r.max_y() - r.min_y() + 1) as usize;
-        let sz = (r.max_z() - r.min_z() + 1) as usize;
-        let volume = sx * sy * sz;
-
-        dbg!(sx);
-        dbg!(sy);
-        dbg!(sz);
-        dbg!(volume);
-        let positions = vec![false; volume];
+impl PaddedBounds {
+    fn new(region: &Region) -> Self {
         Self {
-            positions,
-            region: r,
+            min: Vec3::new(region.min_x() - 1, region.min_y() - 1, region.min_z() - 1),
+            max: Vec3::new(region.max_x() + 1, region.max_y() + 1, region.max_z() + 1),
         }
     }
 
-    fn pos_to_index(&self, pos: &Vec3) -> usize {
-        let sx = (self.region.max_x() - self.region.min_x() + 1) as usize;
-        let sz = (self.region.max_z() - self.region.min_z() + 1) as usize;
-        let ax = (pos.x - self.region.min_x()) as usize;
-        let ay = (pos.y - self.region.min_y()) as usize;
-        let az = (pos.z - self.region.min_z()) as usize;
-        ax + az * sx + ay * sz * sx
+    fn volume(&self) -> usize {
+        let sx = (self.max.x - self.min.x + 1) as usize;
+        let sy = (self.max.y - self.min.y + 1) as usize;
+        let sz = (self.max.z - self.min.z + 1) as usize;
+        sx * sy * sz
     }
 
-    fn insert(&mut self, pos: &Vec3) {
-        let idx = self.pos_to_index(pos);
-        if idx >= self.positions.len() {
-            dbg!("out of bound", pos);
-            dbg!(idx);
-            panic!();
+    fn pos_to_index(&self, pos: &Vec3) -> Option<usize> {
+        if !(self.min.x..=self.max.x).contains(&pos.x)
+            || !(self.min.y..=self.max.y).contains(&pos.y)
+            || !(self.min.z..=self.max.z).contains(&pos.z)
+        {
+            return None;
         }
-        self.positions[idx] = true;
+        let sx = (self.max.x - self.min.x + 1) as usize;
+        let sz = (self.max.z - self.min.z + 1) as usize;
+        let ax = (pos.x - self.min.x) as usize;
+        let ay = (pos.y - self.min.y) as usize;
+        let az = (pos.z - self.min.z) as usize;
+        Some(ax + az * sx + ay * sz * sx)
+    }
+}
+
+// a set of positions in a region and the buffer around it, one bit each
+struct PositionTracker {
+    bits: Vec<u64>,
+    bounds: PaddedBounds,
+}
+
+impl PositionTracker {
+    fn new(region: &Region) -> Self {
+        let bounds = PaddedBounds::new(region);
+        Self {
+            bits: vec![0; Self::bytes(&bounds) / 8],
+            bounds,
+        }
+    }
+
+    // how much memory a tracker for `bounds` takes
+    fn bytes(bounds: &PaddedBounds) -> usize {
+        bounds.volume().div_ceil(64) * 8
+    }
+
+    fn insert(&mut self, pos: &Vec3) {
+        let Some(idx) = self.bounds.pos_to_index(pos) else {
+            panic!("{:?} is out of bounds", pos);
+        };
+        self.bits[idx / 64] |= 1 << (idx % 64);
+    }
+
+    fn positions(&self) -> impl Iterator<Item = Vec3> + '_ {
+        let PaddedBounds { min, max } = self.bounds;
+        iproduct!(min.x..=max.x, min.y..=max.y, min.z..=max.z)
+            .map(|(x, y, z)| Vec3::new(x, y, z))
+            .filter(move |pos| self.contains(pos))
     }
 
     fn contains(&self, pos: &Vec3) -> bool {
-        let idx = self.pos_to_index(pos);
-        if idx >= self.positions.len() {
+        let Some(idx) = self.bounds.pos_to_index(pos) else {
             return false;
-            // dbg!("out of bound", pos);
-            // dbg!(self.positions.len());
-            // dbg!(idx);
-            // panic!();
+        };
+        self.bits[idx / 64] & (1 << (idx % 64)) != 0
+    }
+}
+
+// the direction the flood moved in to get to each position, 3 bits each, so
+// the way back to where it started can be followed
+struct Parents {
+    bits: Vec<u64>,
+    bounds: PaddedBounds,
+}
+
+impl Parents {
+    const PER_WORD: usize = 21;
+
+    fn new(region: &Region) -> Self {
+        let bounds = PaddedBounds::new(region);
+        Self {
+            bits: vec![0; Self::bytes(&bounds) / 8],
+            bounds,
         }
-        self.positions[idx]
+    }
+
+    // how much memory the parents of `bounds` take
+    fn bytes(bounds: &PaddedBounds) -> usize {
+        bounds.volume().div_ceil(Self::PER_WORD) * 8
+    }
+
+    fn insert(&mut self, pos: &Vec3, dir: Direction) {
+        let Some(idx) = self.bounds.pos_to_index(pos) else {
+            panic!("{:?} is out of bounds", pos);
+        };
+        let shift = idx % Self::PER_WORD * 3;
+        // 0 is left for positions without a parent
+        let value = Direction::all().iter().position(|d| *d == dir).unwrap() as u64 + 1;
+        self.bits[idx / Self::PER_WORD] &= !(0b111 << shift);
+        self.bits[idx / Self::PER_WORD] |= value << shift;
+    }
+
+    fn get(&self, pos: &Vec3) -> Option<Vec3> {
+        let idx = self.bounds.pos_to_index(pos)?;
+        let shift = idx % Self::PER_WORD * 3;
+        let value = (self.bits[idx / Self::PER_WORD] >> shift) & 0b111;
+        let dir = Direction::all().get((value as usize).checked_sub(1)?)?;
+        Some(*pos + dir.opposite())
     }
 }
 
-fn optimize_region<'a>(
-    region: &Region<'a>,
-    starting_pos: Vec3,
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum FloodMode {
+    /// move through any gap, like light does
+    Light,
+    /// only go where a player can walk, see `walk.rs`
+    Walk,
+}
+
+/// How the optimizer's flood moves and what it keeps
+#[derive(Clone)]
+struct FloodOptions {
+    mode: FloodMode,
+    // fill the air the flood goes through with a rainbow pattern (light mode only)
     rainbow: bool,
-    inside: Option<Vec3>,
-) -> Result<Region<'a>> {
-    let mut output_region = region.clone();
+    // stop when the flood reaches this block and mark the path to it (light
+    // mode only)
+    inside: Option<String>,
+    shell_thickness: usize,
+    open_faces: Vec<Direction>,
+    // how far to look from the flood for blocks in the line of sight, 0 to disable
+    sight_range: usize,
+    // look at each generation of the light flood in parallel
+    parallel: bool,
+    // run the queue flood too, and compare it with the light flood
+    benchmark: bool,
+    // flood regions that would take more than this many MiB in sections, or
+    // refuse to if they can't be
+    max_memory: Option<usize>,
+}
 
-    let mut q: VecDeque<Node> = VecDeque::new();
-    q.push_back(Node {
-        pos: starting_pos,
-        gen: 0,
-    });
+// optimize `region` in place, returning how many blocks were changed
+fn optimize_region<'a>(
+    region: &mut Region<'a>,
+    starting_positions: &[Vec3],
+    filler: &BlockState<'a>,
+    options: &FloodOptions,
+) -> Result<usize> {
+    // the palette indices of the region, packed, to unpack what the flood
+    // needs from
+    let blocks = PackedBlocks::new(region);
+    let memory = flood_memory(region, &blocks, options) >> 20;
+    // the light flood can go through a region a few layers at a time when
+    // flooding it all at once would take too much
+    let mut in_sections = None;
+    if let Some(max_memory) = options.max_memory.filter(|max_memory| memory > *max_memory) {
+        let Some(layers) = sections::layers_within(region, &blocks, options, max_memory << 20)
+        else {
+            bail!(
+                "Flooding region {} takes about {} MiB, more than the {} MiB allowed",
+                region.name,
+                memory,
+                max_memory
+            );
+        };
+        progress::note(format!(
+            "Flooding region {} in sections of {} layers takes about {} MiB",
+            region.name,
+            layers,
+            sections::memory(region, &blocks, layers) >> 20
+        ));
+        in_sections = Some(layers);
+    } else {
+        progress::note(format!(
+            "Flooding region {} takes about {} MiB",
+            region.name, memory
+        ));
+    }
 
-    // let mut visited: HashSet<Vec3> = HashSet::new();
-    let mut visited = PositionTracker::new(region);
-    visited.insert(&starting_pos);
+    let flood = match options.mode {
+        FloodMode::Walk => {
+            let progress = progress::spinner(format!("Walking region {}", region.name));
+            let (reachable_blocks, seen) = walk::reachable_blocks(
+                region,
+                &blocks,
+                starting_positions,
+                &options.open_faces,
+                options.sight_range,
+            )?;
+            progress.finish_and_clear();
+            Flood {
+                reachable_blocks: Some(reachable_blocks),
+                visited: seen,
+                marks: vec![],
+            }
+        }
+        FloodMode::Light => match in_sections {
+            Some(layers) => sections::flood(region, &blocks, starting_positions, options, layers),
+            None => light_flood(region, &blocks, starting_positions, options),
+        },
+    };
 
-    // let mut reachable_blocks: HashSet<Vec3> = HashSet::new();
-    let mut reachable_blocks = PositionTracker::new(region);
+    let mut changed = 0;
+    if let Some(reachable_blocks) = flood.reachable_blocks {
+        changed += hide_unreachable(
+            region,
+            reachable_blocks,
+            &flood.visited,
+            filler,
+            options.shell_thickness,
+        );
+    }
+    changed += flood.marks.len();
+    for (pos, blockstate) in flood.marks {
+        region.set_block(pos, blockstate);
+    }
+    Ok(changed)
+}
 
-    let mut parents = HashMap::new();
-    let mut light_leaked = false;
+// what a flood found
+struct Flood {
+    // the blocks to keep, `None` if everything should be kept
+    reachable_blocks: Option<PositionTracker>,
+    // where the flood went or looked into; air anywhere else is out of reach
+    visited: PositionTracker,
+    // blocks showing the way the flood went, for `--rainbow` and `--inside`
+    marks: Vec<(Vec3, BlockState<'static>)>,
+}
 
-    let mut lastgen = 0;
+// roughly how many bytes flooding `region` takes, besides the region itself
+// and its packed palette indices in `blocks`, which are counted too
+fn flood_memory(region: &Region, blocks: &PackedBlocks, options: &FloodOptions) -> usize {
+    let bounds = PaddedBounds::new(region);
+    let bytes = blocks.bytes() + Palette::bytes(region);
+    match options.mode {
+        // standing positions, seen cells and reachable blocks
+        FloodMode::Walk => bytes + 3 * PositionTracker::bytes(&bounds),
+        // visited positions, reachable blocks and the way to `inside`
+        FloodMode::Light => {
+            let mut bytes = bytes + 2 * PositionTracker::bytes(&bounds);
+            if options.inside.is_some() {
+                bytes += Parents::bytes(&bounds);
+            }
+            bytes
+        }
+    }
+}
 
+// the light flood as it was before it went one generation at a time: a plain
+// queue, one position after another, looking at the block states themselves
+// instead of a palette; `--benchmark` and the tests check the faster floods
+// against it
+fn queue_flood(
+    region: &Region,
+    starting_positions: &[Vec3],
+    options: &FloodOptions,
+) -> PositionTracker {
+    let open_faces = &options.open_faces;
     let air = BlockState {
         name: Cow::from("minecraft:air"),
         properties: None,
     };
 
-    'bfs: while !q.is_empty() {
-        let Node { pos, gen } = q.pop_front().unwrap();
-        let current_block = if region.contains(&pos) {
+    let mut q: VecDeque<Node> = VecDeque::new();
+    let mut visited = PositionTracker::new(region);
+    for starting_pos in starting_positions {
+        if !visited.contains(starting_pos) {
+            q.push_back(Node {
+                pos: *starting_pos,
+                gen: 0,
+            });
+            visited.insert(starting_pos);
+        }
+    }
+    let mut reachable_blocks = PositionTracker::new(region);
+
+    while let Some(Node { pos, gen }) = q.pop_front() {
+        // starting positions are seen as air
+        let current_block = if gen != 0 && region.contains(&pos) {
             region.get_block(pos)
         } else {
             &air
         };
-
-        if gen != lastgen {
-            dbg!(gen);
-            lastgen = gen;
-        }
-
         for dir in Direction::all() {
             let next_pos = pos + dir;
-
             if visited.contains(&next_pos) {
                 continue;
             }
-
-            // let's extend the BFS to a 1-block buffer around the region, to attempt to reach
-            // blocks that are only reachable by going outside
-            if is_just_outside(&next_pos, region) {
+            if is_just_outside(&next_pos, region, open_faces) {
                 q.push_back(Node {
                     pos: next_pos,
                     gen: gen + 1,
@@ -593,14 +1048,122 @@ fn optimize_region<'a>(
                 visited.insert(&next_pos);
                 continue;
             }
-
             if !region.contains(&next_pos) {
                 continue;
             }
-
             let next_block = region.get_block(next_pos);
+            if can_see(current_block, &dir) && next_block.name != "minecraft:air" {
+                reachable_blocks.insert(&next_pos);
+            }
+            if can_move(current_block, next_block, &dir) {
+                q.push_back(Node {
+                    pos: next_pos,
+                    gen: gen + 1,
+                });
+                visited.insert(&next_pos);
+            }
+        }
+    }
+
+    if options.sight_range > 0 {
+        let palette = Palette::new(region);
+        look_from(
+            region,
+            &palette,
+            &visited,
+            options.sight_range,
+            &mut reachable_blocks,
+        );
+    }
+    reachable_blocks
+}
+
+fn light_flood(
+    region: &Region,
+    blocks: &PackedBlocks,
+    starting_positions: &[Vec3],
+    options: &FloodOptions,
+) -> Flood {
+    let FloodOptions {
+        rainbow,
+        ref open_faces,
+        sight_range,
+        parallel,
+        ..
+    } = *options;
+    let inside = inside_position(region, options);
+
+    // the flood goes one generation at a time: what's next to each position of
+    // the frontier is looked at in parallel, then merged in frontier order, so
+    // the result is the same as a plain breadth-first search
+    let mut frontier: Vec<Vec3> = vec![];
+
+    // let mut visited: HashSet<Vec3> = HashSet::new();
+    let mut visited = PositionTracker::new(region);
+    for starting_pos in starting_positions {
+        if !visited.contains(starting_pos) {
+            frontier.push(*starting_pos);
+            visited.insert(starting_pos);
+        }
+    }
+
+    // let mut reachable_blocks: HashSet<Vec3> = HashSet::new();
+    let mut reachable_blocks = PositionTracker::new(region);
+
+    // only needed to find the way to `inside`
+    let mut parents = inside.map(|_| Parents::new(region));
+    let mut marks = vec![];
+    let mut light_leaked = false;
+
+    let palette = Palette::layers(blocks, region, region.min_y(), region.max_y());
+
+    let progress = progress::spinner(format!("Flooding region {}", region.name));
+    let mut reached = frontier.len();
+
+    let mut gen = 0;
+    'bfs: while !frontier.is_empty() {
+        trace!("Generation {}: {} positions", gen, frontier.len());
+        progress.set_message(format!(
+            "Flooding region {}: generation {}, {} positions reached",
+            region.name, gen, reached
+        ));
+
+        let look_around = |pos: &Vec3| -> Vec<Step> {
+            // starting positions are usually marker blocks that are not really part of
+            // the build, so the flood sees them as air
+            let current_block = if gen != 0 {
+                palette.get(region, pos)
+            } else {
+                palette::AIR
+            };
+            Direction::all()
+                .into_iter()
+                .filter_map(|dir| Step::new(region, &palette, current_block, *pos, dir, open_faces))
+                .filter(|step| !visited.contains(&step.to))
+                .collect()
+        };
+        let steps: Vec<Vec<Step>> = if parallel {
+            frontier.par_iter().map(look_around).collect()
+        } else {
+            frontier.iter().map(look_around).collect()
+        };
 
-            if rainbow && next_block.name == "minecraft:air" {
+        let mut next_frontier = vec![];
+        for step in steps.into_iter().flatten() {
+            // an earlier position of the frontier may have got there first
+            if visited.contains(&step.to) {
+                continue;
+            }
+
+            // let's extend the BFS to a 1-block buffer around the region, to attempt to reach
+            // blocks that are only reachable by going outside
+            if step.outside {
+                next_frontier.push(step.to);
+                visited.insert(&step.to);
+                continue;
+            }
+
+            if rainbow && step.to_air {
                 let rainbow_block = [
                     "minecraft:red_wool",
                     "minecraft:red_concrete",
@@ -619,128 +1182,527 @@ fn optimize_region<'a>(
                     "minecraft:purple_wool",
                     "minecraft:purple_concrete",
                 ][gen % 16];
-                output_region.set_block(
-                    next_pos,
+                marks.push((
+                    step.to,
                     BlockState {
                         name: Cow::from(rainbow_block),
                         properties: None,
                     },
-                );
+                ));
             }
 
-            if can_see(current_block, &dir) && next_block.name != "minecraft:air" {
-                reachable_blocks.insert(&next_pos);
+            if step.seen {
+                reachable_blocks.insert(&step.to);
             }
-            if pos == starting_pos || can_move(current_block, next_block, &dir) {
-                q.push_back(Node {
-                    pos: next_pos,
-                    gen: gen + 1,
-                });
-                if let Some(inside) = inside {
-                    parents.insert(next_pos, pos);
-                    if next_pos == inside {
+            // the inside block is usually a marker that can't be moved into,
+            // so seeing it is enough
+            if step.movable || (step.seen && inside == Some(step.to)) {
+                next_frontier.push(step.to);
+                if let (Some(inside), Some(parents)) = (inside, &mut parents) {
+                    parents.insert(&step.to, step.dir);
+                    if step.to == inside {
                         debug!("reached inside from start block");
                         light_leaked = true;
                         break 'bfs;
                     }
                 }
-                visited.insert(&next_pos);
+                visited.insert(&step.to);
             }
         }
+        reached += next_frontier.len();
+        frontier = next_frontier;
+        gen += 1;
     }
+    progress.finish_and_clear();
+    debug!("Flood reached {} positions in {} generations", reached, gen);
 
-    if light_leaked {
+    if let (true, Some(parents)) = (light_leaked, &parents) {
         let mut current = inside.unwrap();
         loop {
             let Some(parent) = parents.get(&current) else {
                 break;
             };
-            if *parent == current {
-                break;
-            }
-            output_region.set_block(
+            marks.push((
                 current,
                 BlockState {
                     name: Cow::from("minecraft:red_wool"),
                     properties: None,
                 },
-            );
-            current = *parent;
+            ));
+            current = parent;
         }
-        return Ok(output_region);
+        return Flood {
+            reachable_blocks: None,
+            visited,
+            marks,
+        };
+    }
+
+    if sight_range > 0 {
+        look_from(
+            region,
+            &palette,
+            &visited,
+            sight_range,
+            &mut reachable_blocks,
+        );
+    }
+
+    Flood {
+        reachable_blocks: Some(reachable_blocks),
+        visited,
+        marks,
+    }
+}
+
+// where the `--inside` block is in `region`, if it's there
+fn inside_position(region: &Region, options: &FloodOptions) -> Option<Vec3> {
+    let block_id = options.inside.as_ref()?;
+    let pos = region
+        .blocks()
+        .find(|(_, blockstate)| blockstate.name == *block_id)
+        .map(|(pos, _)| pos);
+    if pos.is_none() {
+        warn!("No {} block found in region {}", block_id, region.name);
     }
+    pos
+}
+
+// add to `reachable_blocks` what can be seen within `sight_range` from the
+// positions the light went through
+fn look_from(
+    region: &Region,
+    shapes: &impl Shapes,
+    visited: &PositionTracker,
+    sight_range: usize,
+    reachable_blocks: &mut PositionTracker,
+) {
+    // looking from anywhere else the light went is just like looking through
+    // air, so only look from next to blocks that can be seen through
+    let viewpoints: Vec<Vec3> = visited
+        .positions()
+        .filter(|pos| {
+            Direction::all().into_iter().any(|dir| {
+                let next_block = shapes.get(region, &(*pos + dir));
+                !shapes.is_air(next_block) && !shapes.is_full(next_block)
+            })
+        })
+        .collect();
+    debug!(
+        "Looking for blocks in sight from {} positions",
+        viewpoints.len()
+    );
+    sight::mark_visible(region, shapes, &viewpoints, sight_range, reachable_blocks);
+}
 
-    for (pos, blockstate) in region.blocks() {
+// replace everything that's not in `reachable_blocks` with `filler`, returning
+// how many blocks were replaced; unless `filler` is air, the air the flood
+// didn't get to is filled too, so no pockets are left inside the build
+fn hide_unreachable<'a>(
+    region: &mut Region<'a>,
+    mut reachable_blocks: PositionTracker,
+    visited: &PositionTracker,
+    filler: &BlockState<'a>,
+    shell_thickness: usize,
+) -> usize {
+    // keep everything within `shell_thickness` steps of a visible block, so the
+    // walls stay thicker than the single layer the light can touch
+    if shell_thickness > 0 {
+        let mut shell_q: VecDeque<Node> = reachable_blocks
+            .positions()
+            .map(|pos| Node { pos, gen: 0 })
+            .collect();
+        while let Some(Node { pos, gen }) = shell_q.pop_front() {
+            if gen >= shell_thickness {
+                continue;
+            }
+            for dir in Direction::all() {
+                let next_pos = pos + dir;
+                if !region.contains(&next_pos) || reachable_blocks.contains(&next_pos) {
+                    continue;
+                }
+                reachable_blocks.insert(&next_pos);
+                shell_q.push_back(Node {
+                    pos: next_pos,
+                    gen: gen + 1,
+                });
+            }
+        }
+    }
+
+    // the blocks that aren't kept are the ones with block data to drop, air
+    // has none
+    drop_block_data(region, |region, pos| {
+        region.contains(pos) && !reachable_blocks.contains(pos)
+    });
+
+    let mut replaced = 0;
+    for (x, y, z) in iproduct!(region.x_range(), region.y_range(), region.z_range()) {
+        let pos = Vec3::new(x, y, z);
         if reachable_blocks.contains(&pos) {
             continue;
         }
-        if blockstate.name == "minecraft:air" {
+        let blockstate = region.get_block(pos);
+        if blockstate.name == "minecraft:air"
+            && (filler.name == "minecraft:air" || visited.contains(&pos))
+        {
             continue;
         }
-        debug!("Replacing {} at {:?} with air", blockstate.name, pos);
-        output_region.set_block(pos, air.clone());
+        trace!(
+            "Replacing {} at {:?} with {}",
+            blockstate.name,
+            pos,
+            filler.name
+        );
+        region.set_block(pos, filler.clone());
+        replaced += 1;
     }
-    Ok(output_region)
+    replaced
 }
 
-fn optimize(input: &str, starting_block_id: &str, output: &str) -> Result<()> {
-    let mut starting_pos = None;
+/// Where the optimizer's flood starts from
+enum Start {
+    /// every position of a marker block placed in the build
+    Marker(String),
+    /// a position given by the user
+    Position(Vec3),
+    /// everything around the bounding box of the region
+    Outside,
+}
+
+impl Start {
+    fn positions(&self, region: &Region, open_faces: &[Direction]) -> Vec<Vec3> {
+        match self {
+            Start::Marker(starting_block_id) => region
+                .blocks()
+                .filter(|(_, blockstate)| blockstate.name == *starting_block_id)
+                .map(|(pos, _)| pos)
+                .collect(),
+            Start::Position(pos) => {
+                if region.contains(pos) || is_just_outside(pos, region, open_faces) {
+                    vec![*pos]
+                } else {
+                    vec![]
+                }
+            }
+            Start::Outside => outside_shell(region, open_faces),
+        }
+    }
+}
+
+// parse a position written as `x,y,z`
+fn parse_vec3(s: &str) -> Result<Vec3> {
+    let coords = s
+        .split(',')
+        .map(|c| c.trim().parse::<i32>())
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Invalid position {}", s))?;
+    let &[x, y, z] = &coords[..] else {
+        bail!("Position {} should be written as x,y,z", s);
+    };
+    Ok(Vec3::new(x, y, z))
+}
+
+// returns how many blocks were changed
+fn optimize(
+    input: &str,
+    starts: &[Start],
+    output: &str,
+    filler: &Filler,
+    options: &FloodOptions,
+    metadata: &Metadata,
+) -> Result<usize> {
     debug!("Reading schematic {}... ", input);
-    let schematic = Litematic::read_file(input)?;
+    let mut output_schematic = formats::read(input)?;
     debug!("done.");
 
-    let mut output_schematic = Litematic::new(
-        Path::new(output)
-            .file_name()
-            .context("filename required")?
-            .to_string_lossy()
-            .replace(".litematic", "")
-            .into(),
-        schematic.description,
-        schematic.author,
-    );
+    let filler = filler.resolve(&output_schematic)?;
+    debug!("Filling hidden blocks with {}", filler.name);
 
-    for region in schematic.regions.iter() {
-        for (pos, blockstate) in region.blocks() {
-            if blockstate.name == starting_block_id {
-                starting_pos = Some(pos);
-            }
+    let regions = std::mem::take(&mut output_schematic.regions);
+    metadata.apply(&mut output_schematic);
+
+    let mut changed = 0;
+    for mut region in regions {
+        let starting_positions: Vec<Vec3> = starts
+            .iter()
+            .flat_map(|start| start.positions(&region, &options.open_faces))
+            .collect();
+        if starting_positions.is_empty() {
+            bail!("No starting position found in region {}", region.name);
         }
-        let Some(starting_pos) = starting_pos else {
-            bail!("Starting block id {} not found in region {}", starting_block_id, region.name);
+        debug!(
+            "Starting from {} positions in region {}",
+            starting_positions.len(),
+            region.name
+        );
+
+        changed += if options.benchmark {
+            benchmark_region(&mut region, &starting_positions, &filler, options)?
+        } else {
+            optimize_region(&mut region, &starting_positions, &filler, options)?
         };
+        output_schematic.regions.push(region);
+    }
+
+    for note in formats::write(&output_schematic, output)? {
+        warn!("{}", note);
+    }
 
-        let optimized_region =
-            // optimize_region(region, starting_pos, false, Some(Vec3::new(7, 1, 7)))?;
-            optimize_region(region, starting_pos, false, None)?;
-        output_schematic.regions.push(optimized_region);
+    Ok(changed)
+}
+
+// flood `region` with the queue flood and with the light flood, timing both,
+// make sure they reach the same blocks, then optimize it
+fn benchmark_region<'a>(
+    region: &mut Region<'a>,
+    starting_positions: &[Vec3],
+    filler: &BlockState<'a>,
+    options: &FloodOptions,
+) -> Result<usize> {
+    let start = Instant::now();
+    let expected = queue_flood(region, starting_positions, options);
+    let queue_time = start.elapsed();
+
+    let start = Instant::now();
+    let flood = light_flood(
+        region,
+        &PackedBlocks::new(region),
+        starting_positions,
+        options,
+    );
+    let flood_time = start.elapsed();
+
+    println!(
+        "Region {}: queue {:.2?}, {} {:.2?} ({:.1}x)",
+        region.name,
+        queue_time,
+        if options.parallel {
+            "parallel"
+        } else {
+            "sequential"
+        },
+        flood_time,
+        queue_time.as_secs_f64() / flood_time.as_secs_f64()
+    );
+    let expected: Vec<Vec3> = expected.positions().collect();
+    let reached: Vec<Vec3> = flood
+        .reachable_blocks
+        .map_or(vec![], |reached| reached.positions().collect());
+    if reached != expected {
+        // positions come in order, so the first one that differs is missing
+        // from one of them
+        let i = expected
+            .iter()
+            .zip(&reached)
+            .position(|(a, b)| a != b)
+            .unwrap_or(expected.len().min(reached.len()));
+        if let Some(pos) = expected.get(i).or(reached.get(i)) {
+            bail!(
+                "The flood differs from the queue flood at {},{},{}",
+                pos.x,
+                pos.y,
+                pos.z
+            );
+        }
     }
 
-    output_schematic.write_file(output)?;
+    optimize_region(region, starting_positions, filler, options)
+}
 
+fn convert(input: &str, output: &str) -> Result<()> {
+    let schematic = formats::read(input)?;
+    let notes = formats::write(&schematic, output)?;
+
+    if notes.is_empty() {
+        println!("Converted {} to {}", input, output);
+    } else {
+        println!("Converted {} to {}, but:", input, output);
+        for note in notes {
+            println!(" - {}", note);
+        }
+    }
     Ok(())
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    env_logger::init();
+fn convert_command(args: &[String]) -> Result<()> {
+    let [input, output] = args else {
+        bail!("usage: optimatica convert <input> <output>");
+    };
+    convert(input, output)
+}
 
-    let command = env::args().nth(1).unwrap();
+fn optimize_command(args: impl Iterator<Item = String>) -> Result<()> {
+    let mut positional = vec![];
+    let mut filler = Filler::Air;
+    let mut metadata = Metadata::default();
+    let mut in_place = InPlace::default();
+    let mut starts = vec![];
+    let mut options = FloodOptions {
+        mode: FloodMode::Light,
+        rainbow: false,
+        inside: None,
+        shell_thickness: 0,
+        open_faces: Direction::all().to_vec(),
+        sight_range: 0,
+        parallel: true,
+        benchmark: false,
+        max_memory: None,
+    };
 
-    if command == "materials" {
-        let filename = env::args().nth(2).unwrap();
-        materials(&filename)?;
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--fill-with" => {
+                let value = args.next().context("--fill-with requires a block state")?;
+                filler = Filler::Block(parse_blockstate(&value)?);
+            }
+            "--fill-cheapest" => filler = Filler::Cheapest,
+            "--shell-thickness" => {
+                let value = args.next().context("--shell-thickness requires a number")?;
+                options.shell_thickness = value
+                    .parse()
+                    .with_context(|| format!("Invalid shell thickness {}", value))?;
+            }
+            "--start" => {
+                let value = args.next().context("--start requires a block id")?;
+                starts.push(Start::Marker(value));
+            }
+            "--start-pos" => {
+                let value = args.next().context("--start-pos requires a position")?;
+                starts.push(Start::Position(parse_vec3(&value)?));
+            }
+            "--from-outside" => starts.push(Start::Outside),
+            "--open-faces" => {
+                let value = args
+                    .next()
+                    .context("--open-faces requires a list of faces")?;
+                options.open_faces = value
+                    .split(',')
+                    .map(|face| Direction::from_name(face.trim()))
+                    .collect::<Result<_>>()?;
+            }
+            "--walk" => options.mode = FloodMode::Walk,
+            "--inside" => {
+                let value = args.next().context("--inside requires a block id")?;
+                options.inside = Some(value);
+            }
+            "--sequential" => options.parallel = false,
+            "--benchmark" => options.benchmark = true,
+            "--max-memory" => {
+                let value = args.next().context("--max-memory requires a size in MiB")?;
+                options.max_memory = Some(
+                    value
+                        .parse()
+                        .with_context(|| format!("Invalid memory size {}", value))?,
+                );
+            }
+            "--line-of-sight" => {
+                let value = args.next().context("--line-of-sight requires a range")?;
+                options.sight_range = value
+                    .parse()
+                    .with_context(|| format!("Invalid range {}", value))?;
+            }
+            x if metadata.parse_arg(x, &mut args)? => {}
+            x if in_place.parse_arg(x) => {}
+            x if x.starts_with("--") => bail!("Unknown option {}", x),
+            _ => positional.push(arg),
+        }
+    }
+
+    let (input, output) = in_place.paths("optimize", &positional)?;
+    if options.benchmark && (options.mode == FloodMode::Walk || options.inside.is_some()) {
+        bail!("--benchmark only works with the light flood, without --inside");
+    }
+    if starts.is_empty() {
+        starts.push(Start::Marker(String::from("minecraft:blue_wool")));
+    }
+
+    if batch::is_batch(input) {
+        let output_dir = (!in_place.enabled).then_some(output);
+        return batch::run(input, output_dir, |input, output| {
+            in_place.prepare(input)?;
+            let changed = optimize(input, &starts, output, &filler, &options, &metadata)?;
+            Ok(format!("{} blocks changed, written to {}", changed, output))
+        });
+    }
+    in_place.prepare(input)?;
+    optimize(input, &starts, output, &filler, &options, &metadata)?;
+    Ok(())
+}
+
+fn replace_command(args: impl Iterator<Item = String>) -> Result<()> {
+    let mut positional = vec![];
+    let mut metadata = Metadata::default();
+    let mut in_place = InPlace::default();
+
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            x if metadata.parse_arg(x, &mut args)? => {}
+            x if in_place.parse_arg(x) => {}
+            x if x.starts_with("--") => bail!("Unknown option {}", x),
+            _ => positional.push(arg),
+        }
+    }
+
+    let (input, output) = in_place.paths("replace", &positional)?;
+
+    if batch::is_batch(input) {
+        let output_dir = (!in_place.enabled).then_some(output);
+        return batch::run(input, output_dir, |input, output| {
+            in_place.prepare(input)?;
+            let replaced = replace(input, output, &metadata)?;
+            Ok(format!(
+                "{} blocks replaced, written to {}",
+                replaced, output
+            ))
+        });
     }
+    in_place.prepare(input)?;
+    replace(input, output, &metadata)?;
+    Ok(())
+}
 
-    if command == "replace" {
-        let input = env::args().nth(2).unwrap();
-        let output = env::args().nth(3).unwrap();
-        replace(&input, &output)?;
+fn materials_command(input: &str) -> Result<()> {
+    if batch::is_batch(input) {
+        return batch::run(input, None, |input, _| {
+            Ok(format!("\n{}", materials(input)?.trim_end()))
+        });
     }
+    println!("====== materials =======");
+    print!("{}", materials(input)?);
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    // `--quiet` and `--verbose` go with any command
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let mut log_level = "warn";
+    args.retain(|arg| match arg.as_str() {
+        "--quiet" => {
+            log_level = "error";
+            progress::hide();
+            false
+        }
+        "--verbose" => {
+            log_level = "debug";
+            false
+        }
+        _ => true,
+    });
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(log_level)).init();
 
-    if command == "optimize" {
-        let input = env::args().nth(2).unwrap();
-        let output = env::args().nth(3).unwrap();
-        optimize(&input, "minecraft:blue_wool", &output)?;
+    let command = args.remove(0);
+
+    if command == "materials" {
+        materials_command(&args[0])?;
+    } else if command == "replace" {
+        replace_command(args.into_iter())?;
+    } else if command == "convert" {
+        convert_command(&args)?;
+    } else if command == "optimize" {
+        optimize_command(args.into_iter())?;
     }
 
     Ok(())
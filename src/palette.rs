@@ -0,0 +1,235 @@
+//  A region's blocks as indices into a palette of what the floods and the line
+//  of sight need to know about them: whether they're air, which of their
+//  octants are filled and which octants of each of their faces are. The shape
+//  of each block state in the region's own palette is worked out once, instead
+//  of every time a block is looked at. A palette can also be built for just a
+//  few layers, from the region's blocks kept packed, for floods done in
+//  sections; the packed blocks can be looked at directly too.
+
+use itertools::iproduct;
+use rustmatica::{util::Vec3, BlockState, Region};
+
+use crate::{
+    formats::{bits_per_block, packed_index},
+    side, BlockShape, Direction,
+};
+
+const FULL_FACE: u8 = 0b1111;
+
+/// The filled octants on each face of a block, indexed by `Direction`, one bit
+/// per octant in the order `side` lists them
+#[derive(Clone, Copy, Default)]
+pub(crate) struct FaceMasks([u8; 6]);
+
+impl FaceMasks {
+    pub(crate) fn from(shape: &BlockShape) -> Self {
+        let mut masks = [0; 6];
+        for dir in Direction::all() {
+            for (i, c) in side(dir).into_iter().enumerate() {
+                if shape.corners[c.x][c.y][c.z] {
+                    masks[dir as usize] |= 1 << i;
+                }
+            }
+        }
+        Self(masks)
+    }
+
+    // whether the light can see out of a block through the face towards `dir`
+    pub(crate) fn can_see(&self, dir: Direction) -> bool {
+        self.0[dir as usize] != FULL_FACE
+    }
+
+    // whether the light can move from a block into `to`, i.e. some octant on
+    // the face between them is empty in both; octants at the same place on
+    // both sides of a face have the same bit, since `side` lists them in the
+    // same order
+    pub(crate) fn can_move(&self, to: &Self, dir: Direction) -> bool {
+        (self.0[dir as usize] | to.0[dir.opposite() as usize]) != FULL_FACE
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct PaletteEntry {
+    air: bool,
+    // one bit per octant, see `octant_bit`
+    octants: u8,
+    masks: FaceMasks,
+}
+
+impl PaletteEntry {
+    fn from(blockstate: &BlockState) -> Self {
+        let shape = BlockShape::from(blockstate);
+        let mut octants = 0;
+        for (x, y, z) in iproduct!(0..2, 0..2, 0..2) {
+            if shape.corners[x][y][z] {
+                octants |= octant_bit([x, y, z]);
+            }
+        }
+        Self {
+            air: blockstate.name == "minecraft:air",
+            octants,
+            masks: FaceMasks::from(&shape),
+        }
+    }
+}
+
+fn octant_bit([x, y, z]: [usize; 3]) -> u8 {
+    1 << (x << 2 | y << 1 | z)
+}
+
+/// Blocks looked up by their palette index
+pub(crate) trait Shapes: Sync {
+    fn entries(&self) -> &[PaletteEntry];
+
+    /// The palette index of the block at `pos`, air if it's outside the region
+    fn get(&self, region: &Region, pos: &Vec3) -> u32;
+
+    fn is_air(&self, index: u32) -> bool {
+        self.entries()[index as usize].air
+    }
+
+    fn masks(&self, index: u32) -> &FaceMasks {
+        &self.entries()[index as usize].masks
+    }
+
+    // whether the octant at `octant`, 0 or 1 along each axis, is filled
+    fn is_filled(&self, index: u32, octant: [usize; 3]) -> bool {
+        self.entries()[index as usize].octants & octant_bit(octant) != 0
+    }
+
+    fn is_empty(&self, index: u32) -> bool {
+        self.entries()[index as usize].octants == 0
+    }
+
+    fn is_full(&self, index: u32) -> bool {
+        self.entries()[index as usize].octants == u8::MAX
+    }
+}
+
+/// A region's blocks as indices into its own palette, packed the way
+/// litematica stores them, to build `Palette`s of some of its layers from
+pub(crate) struct PackedBlocks {
+    // `AIR` comes first, so the region's indices are shifted by 1
+    entries: Vec<PaletteEntry>,
+    states: Vec<i64>,
+    bits: usize,
+}
+
+impl PackedBlocks {
+    pub(crate) fn new(region: &Region) -> Self {
+        let raw = region.to_raw();
+        let mut entries = vec![PaletteEntry {
+            air: true,
+            octants: 0,
+            masks: FaceMasks::default(),
+        }];
+        entries.extend(raw.block_state_palette.iter().map(PaletteEntry::from));
+        Self {
+            entries,
+            bits: bits_per_block(raw.block_state_palette.len()),
+            // taken from the raw region rather than copied, so they're only in
+            // memory once
+            states: raw.block_states.into_inner(),
+        }
+    }
+
+    // how much memory the packed indices take
+    pub(crate) fn bytes(&self) -> usize {
+        self.states.len() * std::mem::size_of::<i64>()
+    }
+}
+
+impl Shapes for PackedBlocks {
+    fn entries(&self) -> &[PaletteEntry] {
+        &self.entries
+    }
+
+    fn get(&self, region: &Region, pos: &Vec3) -> u32 {
+        if !region.contains(pos) {
+            return AIR;
+        }
+        let size_x = (region.max_x() - region.min_x() + 1) as usize;
+        let size_z = (region.max_z() - region.min_z() + 1) as usize;
+        let x = (pos.x - region.min_x()) as usize;
+        let y = (pos.y - region.min_y()) as usize;
+        let z = (pos.z - region.min_z()) as usize;
+        packed_index(&self.states, self.bits, (y * size_z + z) * size_x + x) as u32 + 1
+    }
+}
+
+pub(crate) struct Palette {
+    entries: Vec<PaletteEntry>,
+    indices: Vec<u32>,
+    min: Vec3,
+    size_x: usize,
+    size_z: usize,
+}
+
+/// The index of air, which is also what everything outside the region is
+pub(crate) const AIR: u32 = 0;
+
+impl Palette {
+    pub(crate) fn new(region: &Region) -> Self {
+        Self::layers(
+            &PackedBlocks::new(region),
+            region,
+            region.min_y(),
+            region.max_y(),
+        )
+    }
+
+    /// The palette of the layers of `region` from `min_y` to `max_y`; the
+    /// blocks of other layers can't be looked at
+    pub(crate) fn layers(blocks: &PackedBlocks, region: &Region, min_y: i32, max_y: i32) -> Self {
+        let size_x = (region.max_x() - region.min_x() + 1) as usize;
+        let size_y = (max_y - min_y + 1) as usize;
+        let size_z = (region.max_z() - region.min_z() + 1) as usize;
+
+        // packed indices go in the same order as `indices`
+        let first = (min_y - region.min_y()) as usize * size_x * size_z;
+        let indices = (first..first + size_x * size_y * size_z)
+            .map(|i| packed_index(&blocks.states, blocks.bits, i) as u32 + 1)
+            .collect();
+
+        Self {
+            entries: blocks.entries.clone(),
+            indices,
+            min: Vec3::new(region.min_x(), min_y, region.min_z()),
+            size_x,
+            size_z,
+        }
+    }
+
+    // how much memory the palette indices of `region` take
+    pub(crate) fn bytes(region: &Region) -> usize {
+        Self::layers_bytes(region, (region.max_y() - region.min_y() + 1) as usize)
+    }
+
+    // how much memory the palette indices of `layers` layers of `region` take
+    pub(crate) fn layers_bytes(region: &Region, layers: usize) -> usize {
+        let size_x = (region.max_x() - region.min_x() + 1) as usize;
+        let size_z = (region.max_z() - region.min_z() + 1) as usize;
+        size_x * layers * size_z * std::mem::size_of::<u32>()
+    }
+
+    fn pos_to_index(&self, pos: &Vec3) -> usize {
+        let x = (pos.x - self.min.x) as usize;
+        let y = (pos.y - self.min.y) as usize;
+        let z = (pos.z - self.min.z) as usize;
+        (y * self.size_z + z) * self.size_x + x
+    }
+}
+
+impl Shapes for Palette {
+    fn entries(&self) -> &[PaletteEntry] {
+        &self.entries
+    }
+
+    fn get(&self, region: &Region, pos: &Vec3) -> u32 {
+        if region.contains(pos) {
+            self.indices[self.pos_to_index(pos)]
+        } else {
+            AIR
+        }
+    }
+}
@@ -0,0 +1,70 @@
+//  Progress bars for the slow parts: reading, flooding and writing. They're
+//  drawn on stderr, and hidden with `--quiet` or while processing a batch,
+//  where only the progress over the whole batch is shown. Notes about what's
+//  about to happen go to stderr too, and are hidden the same way, so they don't
+//  break up the batch's progress bar.
+
+use indicatif::{ProgressBar, ProgressStyle};
+use std::{
+    borrow::Cow,
+    fmt::Display,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+static HIDDEN: AtomicBool = AtomicBool::new(false);
+static IN_BATCH: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn hide() {
+    HIDDEN.store(true, Ordering::Relaxed);
+}
+
+pub(crate) fn set_in_batch(in_batch: bool) {
+    IN_BATCH.store(in_batch, Ordering::Relaxed);
+}
+
+/// Something the user should know before it happens, like how much memory a
+/// flood is going to take
+pub(crate) fn note(message: impl Display) {
+    if !HIDDEN.load(Ordering::Relaxed) && !IN_BATCH.load(Ordering::Relaxed) {
+        eprintln!("{}", message);
+    }
+}
+
+fn styled(bar: ProgressBar, template: &str) -> ProgressBar {
+    bar.set_style(ProgressStyle::with_template(template).expect("valid progress template"));
+    bar
+}
+
+/// Something that takes a while, without a known length
+pub(crate) fn spinner(message: impl Into<Cow<'static, str>>) -> ProgressBar {
+    if HIDDEN.load(Ordering::Relaxed) || IN_BATCH.load(Ordering::Relaxed) {
+        return ProgressBar::hidden();
+    }
+    let bar = styled(ProgressBar::new_spinner(), "{spinner} {msg} ({elapsed})");
+    bar.set_message(message);
+    bar.enable_steady_tick(Duration::from_millis(100));
+    bar
+}
+
+/// Something that goes through `len` steps
+pub(crate) fn bar(len: u64, message: impl Into<Cow<'static, str>>) -> ProgressBar {
+    if HIDDEN.load(Ordering::Relaxed) || IN_BATCH.load(Ordering::Relaxed) {
+        return ProgressBar::hidden();
+    }
+    batch_bar(len, message)
+}
+
+/// The progress over a whole batch, shown even while the steps of each part of
+/// it are hidden
+pub(crate) fn batch_bar(len: u64, message: impl Into<Cow<'static, str>>) -> ProgressBar {
+    if HIDDEN.load(Ordering::Relaxed) {
+        return ProgressBar::hidden();
+    }
+    let bar = styled(
+        ProgressBar::new(len),
+        "{msg} [{bar:40}] {pos}/{len} ({elapsed}, ETA {eta})",
+    );
+    bar.set_message(message);
+    bar
+}
@@ -0,0 +1,161 @@
+//  The light flood for regions too big to flood in one go within
+//  `--max-memory`. The region is cut into sections of whole layers, and only
+//  the palette indices of one section (and the layers right above and below
+//  it) are unpacked at a time. Positions the flood gets to in another section
+//  are handed over to it, and sections are flooded again until none has
+//  anything left to go through. The light gets to the same places whatever the
+//  order, so the blocks kept are the same as with `light_flood`, but the way
+//  it went isn't, so the rainbow pattern and `--inside` can't be used.
+
+use log::{debug, trace};
+use rustmatica::{util::Vec3, Region};
+use std::collections::VecDeque;
+
+use crate::{
+    look_from,
+    palette::{self, PackedBlocks, Palette, Shapes},
+    progress, Direction, Flood, FloodMode, FloodOptions, PaddedBounds, PositionTracker, Step,
+};
+
+// what flooding `region` in sections of `layers` layers takes besides the
+// region itself: visited positions, reachable blocks, the packed palette
+// indices and the unpacked ones of a section with a layer above and below it
+pub(crate) fn memory(region: &Region, blocks: &PackedBlocks, layers: usize) -> usize {
+    fixed_memory(region, blocks) + Palette::layers_bytes(region, layers + 2)
+}
+
+fn fixed_memory(region: &Region, blocks: &PackedBlocks) -> usize {
+    2 * PositionTracker::bytes(&PaddedBounds::new(region)) + blocks.bytes()
+}
+
+/// The most layers a section of `region` can have to be flooded within
+/// `max_bytes`, `None` if the flood can't be done in sections or even a single
+/// layer takes too much
+pub(crate) fn layers_within(
+    region: &Region,
+    blocks: &PackedBlocks,
+    options: &FloodOptions,
+    max_bytes: usize,
+) -> Option<usize> {
+    if options.mode != FloodMode::Light || options.rainbow || options.inside.is_some() {
+        return None;
+    }
+    let height = (region.max_y() - region.min_y() + 1) as usize;
+    let layers = max_bytes
+        .checked_sub(fixed_memory(region, blocks))?
+        .checked_div(Palette::layers_bytes(region, 1))?
+        .checked_sub(2)?
+        .min(height);
+    (layers > 0).then_some(layers)
+}
+
+/// Floods `region` from `starting_positions` like `light_flood`, unpacking
+/// `layers` layers of it at a time
+pub(crate) fn flood(
+    region: &Region,
+    blocks: &PackedBlocks,
+    starting_positions: &[Vec3],
+    options: &FloodOptions,
+    layers: usize,
+) -> Flood {
+    let FloodOptions {
+        ref open_faces,
+        sight_range,
+        ..
+    } = *options;
+    let height = (region.max_y() - region.min_y() + 1) as usize;
+    let section_count = height.div_ceil(layers);
+    // positions in the buffer around the region go with the closest section
+    let section_of = |pos: &Vec3| {
+        let y = pos.y.clamp(region.min_y(), region.max_y());
+        (y - region.min_y()) as usize / layers
+    };
+
+    // the positions each section has to go on from, and whether they're
+    // starting positions, which the flood sees as air
+    let mut pending: Vec<Vec<(Vec3, bool)>> = vec![vec![]; section_count];
+    let mut visited = PositionTracker::new(region);
+    for starting_pos in starting_positions {
+        if !visited.contains(starting_pos) {
+            visited.insert(starting_pos);
+            pending[section_of(starting_pos)].push((*starting_pos, true));
+        }
+    }
+
+    let mut reachable_blocks = PositionTracker::new(region);
+
+    let progress = progress::spinner(format!("Flooding region {} in sections", region.name));
+    let mut reached = 0;
+    let mut rounds = 0;
+    while let Some(section) = pending.iter().position(|positions| !positions.is_empty()) {
+        let min_y = region.min_y() + (section * layers) as i32;
+        let max_y = (min_y + layers as i32 - 1).min(region.max_y());
+        trace!(
+            "Section {}, layers {} to {}: {} positions",
+            section,
+            min_y,
+            max_y,
+            pending[section].len()
+        );
+        progress.set_message(format!(
+            "Flooding region {}: section {} of {}, {} positions reached",
+            region.name,
+            section + 1,
+            section_count,
+            reached
+        ));
+        let palette = Palette::layers(
+            blocks,
+            region,
+            (min_y - 1).max(region.min_y()),
+            (max_y + 1).min(region.max_y()),
+        );
+
+        let mut queue: VecDeque<(Vec3, bool)> = std::mem::take(&mut pending[section]).into();
+        while let Some((pos, start)) = queue.pop_front() {
+            reached += 1;
+            let current_block = if start {
+                palette::AIR
+            } else {
+                palette.get(region, &pos)
+            };
+            for dir in Direction::all() {
+                let Some(step) = Step::new(region, &palette, current_block, pos, dir, open_faces)
+                else {
+                    continue;
+                };
+                if visited.contains(&step.to) {
+                    continue;
+                }
+                if step.seen {
+                    reachable_blocks.insert(&step.to);
+                }
+                if step.outside || step.movable {
+                    visited.insert(&step.to);
+                    let next_section = section_of(&step.to);
+                    if next_section == section {
+                        queue.push_back((step.to, false));
+                    } else {
+                        pending[next_section].push((step.to, false));
+                    }
+                }
+            }
+        }
+        rounds += 1;
+    }
+    progress.finish_and_clear();
+    debug!(
+        "Flood reached {} positions in {} rounds of {} sections",
+        reached, rounds, section_count
+    );
+
+    if sight_range > 0 {
+        look_from(region, blocks, &visited, sight_range, &mut reachable_blocks);
+    }
+
+    Flood {
+        reachable_blocks: Some(reachable_blocks),
+        visited,
+        marks: vec![],
+    }
+}
@@ -0,0 +1,105 @@
+//  Line of sight: the flood only keeps the blocks that touch the air it went
+//  through, but from there a player can see further, e.g. past a slab or the
+//  open half of some stairs. This casts rays from a set of viewpoints towards
+//  every position on the surface of a cube around them, and keeps every block
+//  the rays touch until they hit a filled sub-block (see `BlockShape`).
+//
+//  Rays walk the grid of sub-blocks one cell at a time, the way Amanatides and
+//  Woo's voxel traversal does, so they go through every sub-block they cross.
+//
+//  This is expensive: every viewpoint casts rays to the whole surface of the
+//  cube, so the cost grows with the cube of the range. Viewpoints are looked
+//  from in parallel.
+
+use itertools::iproduct;
+use rayon::prelude::*;
+use rustmatica::{util::Vec3, Region};
+
+use crate::{palette::Shapes, progress, PositionTracker};
+
+pub(crate) fn mark_visible(
+    region: &Region,
+    shapes: &impl Shapes,
+    viewpoints: &[Vec3],
+    range: usize,
+    visible: &mut PositionTracker,
+) {
+    let range = range as i32;
+    let progress = progress::bar(viewpoints.len() as u64, "Looking for blocks in sight");
+    let seen: Vec<Vec<Vec3>> = viewpoints
+        .par_iter()
+        .map(|viewpoint| {
+            let mut seen = vec![];
+            for target in cube_surface(*viewpoint, range) {
+                cast(region, shapes, *viewpoint, target, &mut seen);
+            }
+            progress.inc(1);
+            seen
+        })
+        .collect();
+    progress.finish_and_clear();
+    for pos in seen.iter().flatten() {
+        visible.insert(pos);
+    }
+}
+
+fn cube_surface(center: Vec3, r: i32) -> impl Iterator<Item = Vec3> {
+    iproduct!(-r..=r, -r..=r, -r..=r)
+        .filter(move |(x, y, z)| x.abs() == r || y.abs() == r || z.abs() == r)
+        .map(move |(x, y, z)| Vec3::new(center.x + x, center.y + y, center.z + z))
+}
+
+// cast a ray from the center of `from` to the center of `to`, adding the
+// blocks it touches to `seen`
+fn cast(region: &Region, shapes: &impl Shapes, from: Vec3, to: Vec3, seen: &mut Vec<Vec3>) {
+    // in sub-blocks, where the center of a block is the corner between its
+    // eight sub-blocks
+    let start = [2 * from.x + 1, 2 * from.y + 1, 2 * from.z + 1].map(f64::from);
+    let end = [2 * to.x + 1, 2 * to.y + 1, 2 * to.z + 1].map(f64::from);
+
+    let mut cell = start.map(|s| s.floor() as i32);
+    let mut step = [0; 3];
+    // how far along the ray the next cell boundary on each axis is, and how
+    // far apart those boundaries are, as fractions of the whole ray
+    let mut t_max = [f64::INFINITY; 3];
+    let mut t_delta = [f64::INFINITY; 3];
+    for axis in 0..3 {
+        let d = end[axis] - start[axis];
+        if d > 0.0 {
+            step[axis] = 1;
+            t_max[axis] = (f64::from(cell[axis] + 1) - start[axis]) / d;
+            t_delta[axis] = 1.0 / d;
+        } else if d < 0.0 {
+            step[axis] = -1;
+            t_max[axis] = (f64::from(cell[axis]) - start[axis]) / d;
+            t_delta[axis] = -1.0 / d;
+        }
+    }
+
+    loop {
+        let block = Vec3::new(
+            cell[0].div_euclid(2),
+            cell[1].div_euclid(2),
+            cell[2].div_euclid(2),
+        );
+        // everything outside the region is air
+        let index = shapes.get(region, &block);
+        if !shapes.is_air(index) {
+            if seen.last() != Some(&block) {
+                seen.push(block);
+            }
+            if shapes.is_filled(index, cell.map(|c| c.rem_euclid(2) as usize)) {
+                return;
+            }
+        }
+
+        let axis = (0..3)
+            .min_by(|a, b| t_max[*a].total_cmp(&t_max[*b]))
+            .unwrap();
+        if t_max[axis] > 1.0 {
+            return;
+        }
+        cell[axis] += step[axis];
+        t_max[axis] += t_delta[axis];
+    }
+}
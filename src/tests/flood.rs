@@ -0,0 +1,66 @@
+//  Checks that the light flood, sequential or parallel, and the one going
+//  through a region in sections, reach the same blocks as the queue flood it
+//  replaced, on the fixtures and on boxes with slabs and stairs.
+
+use rustmatica::{util::Vec3, Region};
+use std::path::PathBuf;
+
+use super::optimize::{block, options, sealed_box, HOLE};
+use crate::{
+    formats, light_flood, palette::PackedBlocks, progress, queue_flood, sections, Direction,
+    FloodMode, FloodOptions, Start,
+};
+
+fn assert_same_as_reference(region: &Region, name: &str) {
+    progress::hide();
+    let starts = Start::Outside.positions(region, &Direction::all());
+    let expected: Vec<Vec3> = queue_flood(region, &starts, &options(FloodMode::Light))
+        .positions()
+        .collect();
+    for parallel in [false, true] {
+        let options = FloodOptions {
+            parallel,
+            ..options(FloodMode::Light)
+        };
+        let flood = light_flood(region, &PackedBlocks::new(region), &starts, &options);
+        let reached: Vec<Vec3> = flood.reachable_blocks.unwrap().positions().collect();
+        assert_eq!(reached, expected, "{} (parallel: {})", name, parallel);
+    }
+    let blocks = PackedBlocks::new(region);
+    for layers in [1, 2] {
+        let flood = sections::flood(region, &blocks, &starts, &options(FloodMode::Light), layers);
+        let reached: Vec<Vec3> = flood.reachable_blocks.unwrap().positions().collect();
+        assert_eq!(reached, expected, "{} (sections of {})", name, layers);
+    }
+}
+
+#[test]
+fn flood_matches_the_reference_on_the_fixtures() {
+    for name in ["sealed_box", "leaky_box", "markers"] {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(format!("{}.litematic", name));
+        let schematic = formats::read(path.to_str().unwrap()).unwrap();
+        for region in schematic.regions.iter() {
+            assert_same_as_reference(region, name);
+        }
+    }
+}
+
+#[test]
+fn flood_matches_the_reference_through_slabs_and_stairs() {
+    let walls = [
+        "minecraft:air",
+        "minecraft:oak_slab[type=bottom]",
+        "minecraft:oak_slab[type=top]",
+        "minecraft:oak_stairs[facing=west,half=bottom,shape=straight]",
+        "minecraft:oak_stairs[facing=north,half=top,shape=inner_left]",
+        "minecraft:oak_stairs[facing=south,half=bottom,shape=outer_right]",
+    ];
+    for wall in walls {
+        let mut region = sealed_box();
+        region.set_block(HOLE, block(wall));
+        region.set_block(Vec3::new(1, 2, 2), block("minecraft:oak_slab[type=top]"));
+        assert_same_as_reference(&region, wall);
+    }
+}
@@ -0,0 +1,469 @@
+//  Schematics written by hand into a temporary file and read back, checking
+//  what the readers make of them.
+
+use fastnbt::{ByteArray, IntArray, Value};
+use flate2::read::GzDecoder;
+use itertools::iproduct;
+use rustmatica::{util::Vec3, BlockState, Litematic, Region};
+use std::{borrow::Cow, collections::HashMap, env, fs, io::Read};
+
+use crate::{format_blockstate, formats, parse_blockstate, progress, BlockShape};
+
+// a 2×1×1 MCEdit schematic with stairs: oak stairs facing north at x=0 and
+// facing east at x=1, both on the bottom half
+fn legacy_stairs() -> HashMap<String, Value> {
+    HashMap::from([
+        ("Materials".to_owned(), Value::String("Alpha".to_owned())),
+        ("Width".to_owned(), Value::Short(2)),
+        ("Height".to_owned(), Value::Short(1)),
+        ("Length".to_owned(), Value::Short(1)),
+        (
+            "Blocks".to_owned(),
+            Value::ByteArray(ByteArray::new(vec![53, 53])),
+        ),
+        (
+            "Data".to_owned(),
+            Value::ByteArray(ByteArray::new(vec![3, 0])),
+        ),
+    ])
+}
+
+fn shape(s: &str) -> [[[bool; 2]; 2]; 2] {
+    BlockShape::from(&parse_blockstate(s).unwrap()).corners
+}
+
+#[test]
+fn legacy_stairs_get_their_full_shape() {
+    progress::hide();
+    let path = env::temp_dir().join("optimatica-legacy-stairs.schematic");
+    fs::write(&path, fastnbt::to_bytes(&legacy_stairs()).unwrap()).unwrap();
+    let schematic = formats::read(path.to_str().unwrap()).unwrap();
+    fs::remove_file(&path).unwrap();
+    let region = &schematic.regions[0];
+
+    // the stairs facing east have the stairs facing north behind them, which
+    // makes an inner corner; the others have nothing around to connect to
+    assert_eq!(
+        BlockShape::from(region.get_block(Vec3::new(0, 0, 0))).corners,
+        shape("minecraft:oak_stairs[facing=north,half=bottom,shape=straight]")
+    );
+    assert_eq!(
+        BlockShape::from(region.get_block(Vec3::new(1, 0, 0))).corners,
+        shape("minecraft:oak_stairs[facing=east,half=bottom,shape=inner_left]")
+    );
+}
+
+// a schematic with a single region away from the origin, holding a few blocks,
+// a chest and an armor stand; with `power_levels`, it also holds redstone wire
+// in every combination of power and connections, so the palette goes over 128
+// entries and its indices take more than one byte
+fn schematic(power_levels: bool) -> Litematic<'static> {
+    let mut schematic = Litematic::new(Cow::from("roundtrip"), Cow::from(""), Cow::from("someone"));
+    schematic.minecraft_data_version = 3465;
+    let mut region = Region::new(
+        Cow::from("roundtrip"),
+        Vec3::new(10, 64, -5),
+        Vec3::new(16, 2, 10),
+    );
+    region.set_block(Vec3::new(0, 0, 0), block("minecraft:stone"));
+    region.set_block(
+        Vec3::new(1, 0, 0),
+        block("minecraft:oak_stairs[facing=east,half=top,shape=straight]"),
+    );
+    region.set_block(
+        Vec3::new(2, 1, 1),
+        block("minecraft:chest[facing=north,type=single,waterlogged=false]"),
+    );
+    region.tile_entities.push(HashMap::from([
+        ("id".to_owned(), Value::String("minecraft:chest".to_owned())),
+        ("x".to_owned(), Value::Int(2)),
+        ("y".to_owned(), Value::Int(1)),
+        ("z".to_owned(), Value::Int(1)),
+        ("Items".to_owned(), Value::List(vec![])),
+    ]));
+    region.entities.push(HashMap::from([
+        (
+            "id".to_owned(),
+            Value::String("minecraft:armor_stand".to_owned()),
+        ),
+        (
+            "Pos".to_owned(),
+            Value::List(vec![
+                Value::Double(1.5),
+                Value::Double(1.0),
+                Value::Double(2.5),
+            ]),
+        ),
+        ("Invisible".to_owned(), Value::Byte(1)),
+    ]));
+    if power_levels {
+        let sides = ["none", "side", "up"];
+        for (i, (power, north, south)) in iproduct!(0..16, sides, sides).enumerate() {
+            let pos = Vec3::new(i as i32 % 16, 0, 1 + i as i32 / 16);
+            region.set_block(
+                pos,
+                block(&format!(
+                    "minecraft:redstone_wire[power={},north={},south={}]",
+                    power, north, south
+                )),
+            );
+        }
+    }
+    schematic.regions.push(region);
+    schematic
+}
+
+fn block(s: &str) -> BlockState<'static> {
+    parse_blockstate(s).unwrap()
+}
+
+// writes `schematic` and reads it back, along with the notes from writing it
+fn write_and_read(schematic: &Litematic, file_name: &str) -> (Litematic<'static>, Vec<String>) {
+    progress::hide();
+    let path = env::temp_dir().join(file_name);
+    let path = path.to_str().unwrap();
+    let notes = formats::write(schematic, path).unwrap();
+    let read = formats::read(path).unwrap();
+    fs::remove_file(path).unwrap();
+    (read, notes)
+}
+
+fn assert_same_region(a: &Region, b: &Region) {
+    for (pos, blockstate) in a.blocks() {
+        assert_eq!(
+            format_blockstate(b.get_block(pos)),
+            format_blockstate(blockstate),
+            "block at {:?}",
+            pos
+        );
+    }
+    assert_eq!(a.tile_entities, b.tile_entities);
+    assert_eq!(a.entities, b.entities);
+}
+
+#[test]
+fn sponge_v2_round_trip() {
+    for power_levels in [false, true] {
+        let schematic = schematic(power_levels);
+        let (read, notes) = write_and_read(&schematic, "optimatica-roundtrip.schem");
+        assert_eq!(notes, Vec::<String>::new());
+        assert_eq!(read.minecraft_data_version, 3465);
+        assert_eq!(read.author, "someone");
+        assert_eq!(read.regions[0].position, schematic.regions[0].position);
+        assert_same_region(&schematic.regions[0], &read.regions[0]);
+    }
+}
+
+#[test]
+fn sponge_keeps_only_the_capitalized_ids() {
+    progress::hide();
+    let path = env::temp_dir().join("optimatica-ids.schem");
+    let path = path.to_str().unwrap();
+    formats::write(&schematic(false), path).unwrap();
+    let mut bytes = vec![];
+    GzDecoder::new(&fs::read(path).unwrap()[..])
+        .read_to_end(&mut bytes)
+        .unwrap();
+    fs::remove_file(path).unwrap();
+    let root: HashMap<String, Value> = fastnbt::from_bytes(&bytes).unwrap();
+    for key in ["BlockEntities", "Entities"] {
+        let Some(Value::List(list)) = root.get(key) else {
+            panic!("{} missing", key);
+        };
+        for value in list {
+            let Value::Compound(compound) = value else {
+                panic!("{} should hold compounds", key);
+            };
+            assert!(compound.contains_key("Id"), "{}", key);
+            assert!(!compound.contains_key("id"), "{}", key);
+        }
+    }
+}
+
+// a 2×1×1 v3 Sponge schematic: a barrel with its data in a `Data` compound,
+// next to air
+fn sponge_v3() -> HashMap<String, Value> {
+    let blocks = HashMap::from([
+        (
+            "Palette".to_owned(),
+            Value::Compound(HashMap::from([
+                ("minecraft:air".to_owned(), Value::Int(0)),
+                (
+                    "minecraft:barrel[facing=up,open=false]".to_owned(),
+                    Value::Int(1),
+                ),
+            ])),
+        ),
+        (
+            "Data".to_owned(),
+            Value::ByteArray(ByteArray::new(vec![1, 0])),
+        ),
+        (
+            "BlockEntities".to_owned(),
+            Value::List(vec![Value::Compound(HashMap::from([
+                (
+                    "Id".to_owned(),
+                    Value::String("minecraft:barrel".to_owned()),
+                ),
+                (
+                    "Pos".to_owned(),
+                    Value::IntArray(IntArray::new(vec![0, 0, 0])),
+                ),
+                (
+                    "Data".to_owned(),
+                    Value::Compound(HashMap::from([(
+                        "CustomName".to_owned(),
+                        Value::String("\"Loot\"".to_owned()),
+                    )])),
+                ),
+            ]))]),
+        ),
+    ]);
+    HashMap::from([(
+        "Schematic".to_owned(),
+        Value::Compound(HashMap::from([
+            ("Version".to_owned(), Value::Int(3)),
+            ("DataVersion".to_owned(), Value::Int(3700)),
+            ("Width".to_owned(), Value::Short(2)),
+            ("Height".to_owned(), Value::Short(1)),
+            ("Length".to_owned(), Value::Short(1)),
+            (
+                "Offset".to_owned(),
+                Value::IntArray(IntArray::new(vec![-3, 70, 8])),
+            ),
+            ("Blocks".to_owned(), Value::Compound(blocks)),
+        ])),
+    )])
+}
+
+#[test]
+fn sponge_v3_blocks_are_read() {
+    progress::hide();
+    let path = env::temp_dir().join("optimatica-v3.schem");
+    fs::write(&path, fastnbt::to_bytes(&sponge_v3()).unwrap()).unwrap();
+    let schematic = formats::read(path.to_str().unwrap()).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(schematic.minecraft_data_version, 3700);
+    let region = &schematic.regions[0];
+
+    assert_eq!(region.position, Vec3::new(-3, 70, 8));
+    assert_eq!(
+        format_blockstate(region.get_block(Vec3::new(0, 0, 0))),
+        "minecraft:barrel[facing=up,open=false]"
+    );
+    assert_eq!(region.get_block(Vec3::new(1, 0, 0)).name, "minecraft:air");
+    assert_eq!(
+        region.tile_entities,
+        vec![HashMap::from([
+            (
+                "id".to_owned(),
+                Value::String("minecraft:barrel".to_owned())
+            ),
+            ("x".to_owned(), Value::Int(0)),
+            ("y".to_owned(), Value::Int(0)),
+            ("z".to_owned(), Value::Int(0)),
+            (
+                "CustomName".to_owned(),
+                Value::String("\"Loot\"".to_owned())
+            ),
+        ])]
+    );
+}
+
+#[test]
+fn structure_round_trip() {
+    for power_levels in [false, true] {
+        let schematic = schematic(power_levels);
+        let (read, notes) = write_and_read(&schematic, "optimatica-roundtrip.nbt");
+        // structures don't store a name, an author or where they are
+        assert_eq!(notes, vec!["The schematic's name and author were left out"]);
+        assert_eq!(read.minecraft_data_version, 3465);
+        assert_eq!(read.regions[0].position, Vec3::new(0, 0, 0));
+        assert_same_region(&schematic.regions[0], &read.regions[0]);
+    }
+}
+
+// what the MCEdit reader makes of a row of blocks, given as ids and data values
+fn read_legacy(blocks: &[(u8, u8)]) -> Vec<String> {
+    progress::hide();
+    let (ids, data): (Vec<i8>, Vec<i8>) = blocks
+        .iter()
+        .map(|(id, data)| (*id as i8, *data as i8))
+        .unzip();
+    let root = HashMap::from([
+        ("Materials".to_owned(), Value::String("Alpha".to_owned())),
+        ("Width".to_owned(), Value::Short(blocks.len() as i16)),
+        ("Height".to_owned(), Value::Short(1)),
+        ("Length".to_owned(), Value::Short(1)),
+        ("Blocks".to_owned(), Value::ByteArray(ByteArray::new(ids))),
+        ("Data".to_owned(), Value::ByteArray(ByteArray::new(data))),
+    ]);
+    let path = env::temp_dir().join("optimatica-legacy-row.schematic");
+    fs::write(&path, fastnbt::to_bytes(&root).unwrap()).unwrap();
+    let schematic = formats::read(path.to_str().unwrap()).unwrap();
+    fs::remove_file(&path).unwrap();
+    let region = &schematic.regions[0];
+    (0..blocks.len())
+        .map(|x| format_blockstate(region.get_block(Vec3::new(x as i32, 0, 0))))
+        .collect()
+}
+
+#[test]
+fn legacy_data_values_orient_blocks() {
+    let table = [
+        ((23, 0), "minecraft:dispenser[facing=down,triggered=false]"),
+        ((23, 1), "minecraft:dispenser[facing=up,triggered=false]"),
+        ((158, 9), "minecraft:dropper[facing=up,triggered=true]"),
+        ((29, 1), "minecraft:sticky_piston[extended=false,facing=up]"),
+        ((33, 13), "minecraft:piston[extended=true,facing=east]"),
+        ((34, 10), "minecraft:piston_head[facing=north,type=sticky]"),
+        ((66, 7), "minecraft:rail[shape=south_west]"),
+        (
+            (27, 9),
+            "minecraft:powered_rail[powered=true,shape=east_west]",
+        ),
+        (
+            (93, 6),
+            "minecraft:repeater[delay=2,facing=north,powered=false]",
+        ),
+        (
+            (94, 13),
+            "minecraft:repeater[delay=4,facing=west,powered=true]",
+        ),
+        (
+            (149, 5),
+            "minecraft:comparator[facing=west,mode=subtract,powered=false]",
+        ),
+        ((154, 0), "minecraft:hopper[enabled=true,facing=down]"),
+        ((154, 10), "minecraft:hopper[enabled=false,facing=north]"),
+        ((218, 1), "minecraft:observer[facing=up,powered=false]"),
+        (
+            (69, 0),
+            "minecraft:lever[face=ceiling,facing=west,powered=false]",
+        ),
+        (
+            (69, 13),
+            "minecraft:lever[face=floor,facing=north,powered=true]",
+        ),
+        (
+            (77, 3),
+            "minecraft:stone_button[face=wall,facing=south,powered=false]",
+        ),
+        (
+            (143, 5),
+            "minecraft:oak_button[face=floor,facing=north,powered=false]",
+        ),
+        ((107, 6), "minecraft:oak_fence_gate[facing=north,open=true]"),
+        (
+            (183, 3),
+            "minecraft:spruce_fence_gate[facing=east,open=false]",
+        ),
+        ((86, 1), "minecraft:carved_pumpkin[facing=west]"),
+        ((91, 3), "minecraft:jack_o_lantern[facing=east]"),
+        ((198, 0), "minecraft:end_rod[facing=down]"),
+        ((202, 4), "minecraft:purpur_pillar[axis=x]"),
+        ((216, 8), "minecraft:bone_block[axis=z]"),
+        (
+            (106, 5),
+            "minecraft:vine[east=false,north=true,south=true,west=false]",
+        ),
+        ((144, 3), "minecraft:skeleton_wall_skull[facing=south]"),
+        ((145, 7), "minecraft:chipped_anvil[facing=east]"),
+        ((55, 15), "minecraft:redstone_wire[power=15]"),
+        (
+            (131, 6),
+            "minecraft:tripwire_hook[attached=true,facing=north,powered=false]",
+        ),
+        (
+            (120, 4),
+            "minecraft:end_portal_frame[eye=true,facing=south]",
+        ),
+        ((127, 9), "minecraft:cocoa[age=2,facing=west]"),
+        ((219, 4), "minecraft:white_shulker_box[facing=west]"),
+        ((235, 2), "minecraft:white_glazed_terracotta[facing=north]"),
+    ];
+    let blocks: Vec<(u8, u8)> = table.iter().map(|(block, _)| *block).collect();
+    for ((block, expected), read) in table.iter().zip(read_legacy(&blocks)) {
+        assert_eq!(read, *expected, "{}:{}", block.0, block.1);
+    }
+}
+
+#[test]
+fn regions_are_merged_into_single_region_formats() {
+    let mut schematic = Litematic::new(
+        Cow::from("merged"),
+        Cow::from("two regions"),
+        Cow::from("someone"),
+    );
+    let mut a = Region::new(Cow::from("a"), Vec3::new(0, 0, 0), Vec3::new(2, 1, 1));
+    a.set_block(Vec3::new(0, 0, 0), block("minecraft:stone"));
+    let mut b = Region::new(Cow::from("b"), Vec3::new(5, 2, -3), Vec3::new(1, 1, 1));
+    b.set_block(Vec3::new(0, 0, 0), block("minecraft:gold_block"));
+    b.entities.push(HashMap::from([
+        ("id".to_owned(), Value::String("minecraft:pig".to_owned())),
+        (
+            "Pos".to_owned(),
+            Value::List(vec![
+                Value::Double(0.5),
+                Value::Double(0.0),
+                Value::Double(0.5),
+            ]),
+        ),
+    ]));
+    schematic.regions.extend([a, b]);
+
+    let (read, notes) = write_and_read(&schematic, "optimatica-merged.schem");
+    assert_eq!(
+        notes,
+        vec!["2 regions were merged into one, their names and the schematic's description were left out"]
+    );
+    let [region] = &read.regions[..] else {
+        panic!("{} regions read", read.regions.len());
+    };
+    // the merged region goes from the lowest corner of both to the highest
+    assert_eq!(region.position, Vec3::new(0, 0, -3));
+    assert_eq!(region.get_block(Vec3::new(0, 0, 3)).name, "minecraft:stone");
+    assert_eq!(
+        region.get_block(Vec3::new(5, 2, 0)).name,
+        "minecraft:gold_block"
+    );
+    let count = region
+        .blocks()
+        .filter(|(_, blockstate)| blockstate.name != "minecraft:air")
+        .count();
+    assert_eq!(count, 2);
+    assert_eq!(
+        region.entities[0].get("Pos"),
+        Some(&Value::List(vec![
+            Value::Double(5.5),
+            Value::Double(2.0),
+            Value::Double(0.5),
+        ]))
+    );
+
+    let (_, notes) = write_and_read(&schematic, "optimatica-merged.nbt");
+    assert_eq!(
+        notes,
+        vec!["2 regions were merged into one, their names and the schematic's name, author and description were left out"]
+    );
+}
+
+#[test]
+fn what_sponge_leaves_out_is_noted() {
+    let mut schematic = schematic(false);
+    schematic.description = Cow::from("a chest");
+    schematic.regions[0].tile_entities.push(HashMap::from([
+        ("x".to_owned(), Value::Int(0)),
+        ("y".to_owned(), Value::Int(0)),
+        ("z".to_owned(), Value::Int(0)),
+    ]));
+    let (read, notes) = write_and_read(&schematic, "optimatica-notes.schem");
+    assert_eq!(
+        notes,
+        vec![
+            "The schematic's description was left out",
+            "A block entity without an id was left out",
+        ]
+    );
+    assert_eq!(read.regions[0].tile_entities.len(), 1);
+}
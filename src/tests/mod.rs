@@ -0,0 +1,5 @@
+mod flood;
+mod formats;
+mod optimize;
+mod shapes;
+mod walk;
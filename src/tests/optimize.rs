@@ -0,0 +1,233 @@
+//  Small regions built block by block, flooded from outside, checking what the
+//  optimizer hides. Each one is a hollow 5×5×5 stone box with a gold block in
+//  the middle, which should be hidden unless the box leaks.
+
+use itertools::iproduct;
+use rustmatica::{util::Vec3, BlockState, Region};
+use std::borrow::Cow;
+
+use crate::{
+    optimize_region, parse_blockstate, progress, Direction, FloodMode, FloodOptions, Start,
+};
+
+const GOLD: Vec3 = Vec3 { x: 2, y: 2, z: 2 };
+// the middle of the west wall
+pub(super) const HOLE: Vec3 = Vec3 { x: 0, y: 2, z: 2 };
+
+pub(super) fn block(s: &str) -> BlockState<'static> {
+    parse_blockstate(s).unwrap()
+}
+
+pub(super) fn sealed_box() -> Region<'static> {
+    let mut region = Region::new(Cow::from("box"), Vec3::new(0, 0, 0), Vec3::new(5, 5, 5));
+    for (pos, _) in region.clone().blocks() {
+        let on_wall = [pos.x, pos.y, pos.z].iter().any(|c| *c == 0 || *c == 4);
+        if on_wall {
+            region.set_block(pos, block("minecraft:stone"));
+        }
+    }
+    region.set_block(GOLD, block("minecraft:gold_block"));
+    region
+}
+
+// flood `region` from outside, returning how many blocks were hidden
+fn optimize(region: &mut Region<'static>) -> usize {
+    optimize_with(region, options(FloodMode::Light))
+}
+
+// flood `region` from outside, looking for the gold block; returns how many
+// blocks were marked on the way to it
+fn find_leak(region: &mut Region<'static>) -> usize {
+    let options = FloodOptions {
+        inside: Some(String::from("minecraft:gold_block")),
+        ..options(FloodMode::Light)
+    };
+    optimize_with(region, options)
+}
+
+pub(super) fn options(mode: FloodMode) -> FloodOptions {
+    FloodOptions {
+        mode,
+        rainbow: false,
+        inside: None,
+        shell_thickness: 0,
+        open_faces: Direction::all().to_vec(),
+        sight_range: 0,
+        parallel: true,
+        benchmark: false,
+        max_memory: None,
+    }
+}
+
+fn optimize_with(region: &mut Region<'static>, options: FloodOptions) -> usize {
+    progress::hide();
+    let starts = Start::Outside.positions(region, &options.open_faces);
+    optimize_region(region, &starts, &block("minecraft:air"), &options).unwrap()
+}
+
+fn gold_hidden(region: &Region) -> bool {
+    region.get_block(GOLD).name == "minecraft:air"
+}
+
+#[test]
+fn sealed_box_hides_what_is_inside() {
+    let mut region = sealed_box();
+    assert_eq!(optimize(&mut region), 1);
+    assert!(gold_hidden(&region));
+}
+
+#[test]
+fn one_block_leak_keeps_everything() {
+    let mut region = sealed_box();
+    region.set_block(HOLE, block("minecraft:air"));
+    assert_eq!(optimize(&mut region), 0);
+    assert!(!gold_hidden(&region));
+}
+
+#[test]
+fn stairs_in_a_wall_seal_it() {
+    for facing in ["west", "east"] {
+        let mut region = sealed_box();
+        region.set_block(
+            HOLE,
+            block(&format!(
+                "minecraft:oak_stairs[facing={},half=bottom,shape=straight]",
+                facing
+            )),
+        );
+        assert_eq!(optimize(&mut region), 1, "stairs facing {}", facing);
+        assert!(gold_hidden(&region), "stairs facing {}", facing);
+    }
+}
+
+#[test]
+fn slab_in_a_wall_leaks() {
+    let mut region = sealed_box();
+    region.set_block(HOLE, block("minecraft:oak_slab[type=bottom]"));
+    assert_eq!(optimize(&mut region), 0);
+    assert!(!gold_hidden(&region));
+}
+
+#[test]
+fn slab_floors_seal_the_box() {
+    let floors: [&dyn Fn(i32, i32) -> &'static str; 3] = [
+        &|_, _| "bottom",
+        &|_, _| "top",
+        // a checkerboard of bottom and top slabs
+        &|x, z| if (x + z) % 2 == 0 { "bottom" } else { "top" },
+    ];
+    for floor in floors {
+        let mut region = sealed_box();
+        for x in 1..=3 {
+            for z in 1..=3 {
+                let slab = format!("minecraft:oak_slab[type={}]", floor(x, z));
+                region.set_block(Vec3::new(x, 0, z), block(&slab));
+            }
+        }
+        assert_eq!(optimize(&mut region), 1);
+        assert!(gold_hidden(&region));
+    }
+}
+
+#[test]
+fn staggered_slabs_seal_a_wall() {
+    let mut region = sealed_box();
+    // the light gets into the top half of the wall, but the top slab behind it
+    // is in the way
+    region.set_block(HOLE, block("minecraft:oak_slab[type=bottom]"));
+    region.set_block(Vec3::new(1, 2, 2), block("minecraft:oak_slab[type=top]"));
+    assert_eq!(optimize(&mut region), 1);
+    assert!(gold_hidden(&region));
+}
+
+#[test]
+fn leak_through_half_a_stairs_block_is_marked() {
+    let mut region = sealed_box();
+    // the top half of the stairs is only filled on the north side, so there's
+    // a half-block gap right through the wall
+    region.set_block(
+        HOLE,
+        block("minecraft:oak_stairs[facing=north,half=bottom,shape=straight]"),
+    );
+    // the stairs, the air next to them and the gold block
+    assert_eq!(find_leak(&mut region), 3);
+    assert_eq!(region.get_block(HOLE).name, "minecraft:red_wool");
+    assert_eq!(region.get_block(GOLD).name, "minecraft:red_wool");
+}
+
+#[test]
+fn sealed_box_has_no_leak_to_mark() {
+    let mut region = sealed_box();
+    region.set_block(
+        HOLE,
+        block("minecraft:oak_stairs[facing=west,half=bottom,shape=straight]"),
+    );
+    assert_eq!(find_leak(&mut region), 1);
+    assert!(region
+        .blocks()
+        .all(|(_, blockstate)| blockstate.name != "minecraft:red_wool"));
+}
+
+#[test]
+fn gaps_between_stairs_only_leak_where_they_line_up() {
+    // the stairs in the wall leave the south half of their top open; the
+    // stairs behind them leave the same half open, or the other one
+    for (behind, leaks) in [("north", true), ("south", false)] {
+        let mut region = sealed_box();
+        region.set_block(
+            HOLE,
+            block("minecraft:oak_stairs[facing=north,half=bottom,shape=straight]"),
+        );
+        region.set_block(
+            Vec3::new(1, 2, 2),
+            block(&format!(
+                "minecraft:oak_stairs[facing={},half=bottom,shape=straight]",
+                behind
+            )),
+        );
+        if leaks {
+            // both stairs and the gold block
+            assert_eq!(find_leak(&mut region), 3, "stairs facing {}", behind);
+        } else {
+            assert_eq!(find_leak(&mut region), 1, "stairs facing {}", behind);
+            assert!(gold_hidden(&region), "stairs facing {}", behind);
+        }
+    }
+}
+
+#[test]
+fn walking_from_outside_keeps_the_roof() {
+    let mut region = sealed_box();
+    assert_eq!(optimize_with(&mut region, options(FloodMode::Walk)), 1);
+    assert!(gold_hidden(&region));
+    for (x, z) in iproduct!(0..5, 0..5) {
+        assert_eq!(region.get_block(Vec3::new(x, 4, z)).name, "minecraft:stone");
+    }
+}
+
+#[test]
+fn filling_fills_the_air_the_flood_cant_get_to() {
+    progress::hide();
+    let options = options(FloodMode::Light);
+    let stone = block("minecraft:stone");
+
+    let mut region = sealed_box();
+    let starts = Start::Outside.positions(&region, &options.open_faces);
+    // the gold block and the 26 blocks of air around it
+    assert_eq!(
+        optimize_region(&mut region, &starts, &stone, &options).unwrap(),
+        27
+    );
+    assert!(region
+        .blocks()
+        .all(|(_, blockstate)| blockstate.name == "minecraft:stone"));
+
+    // the air the flood goes through stays
+    let mut region = sealed_box();
+    region.set_block(HOLE, block("minecraft:air"));
+    assert_eq!(
+        optimize_region(&mut region, &starts, &stone, &options).unwrap(),
+        0
+    );
+    assert_eq!(region.get_block(HOLE).name, "minecraft:air");
+}
@@ -0,0 +1,224 @@
+//  Invariants of block shapes and of how the flood moves between them, checked
+//  for every pair of air, full blocks, slabs and stairs block states.
+
+use itertools::iproduct;
+
+use crate::{can_move, can_see, parse_blockstate, BlockShape, Direction};
+
+const FACINGS: [&str; 4] = ["north", "east", "south", "west"];
+const HALVES: [&str; 2] = ["bottom", "top"];
+// in the same order as vanilla's `StairsShape`
+const SHAPES: [&str; 5] = [
+    "straight",
+    "inner_left",
+    "inner_right",
+    "outer_left",
+    "outer_right",
+];
+
+fn stairs(facing: &str, half: &str, shape: &str) -> String {
+    format!(
+        "minecraft:oak_stairs[facing={},half={},shape={}]",
+        facing, half, shape
+    )
+}
+
+// air, full blocks, slabs and every stairs block state
+fn all_blocks() -> Vec<String> {
+    let mut blocks: Vec<String> = [
+        "minecraft:air",
+        "minecraft:stone",
+        "minecraft:oak_slab[type=bottom]",
+        "minecraft:oak_slab[type=top]",
+        "minecraft:oak_slab[type=double]",
+    ]
+    .map(String::from)
+    .to_vec();
+    blocks.extend(
+        iproduct!(FACINGS, HALVES, SHAPES).map(|(facing, half, shape)| stairs(facing, half, shape)),
+    );
+    blocks
+}
+
+// a quarter turn clockwise, seen from above
+fn rotate(dir: Direction) -> Direction {
+    match dir {
+        Direction::North => Direction::East,
+        Direction::East => Direction::South,
+        Direction::South => Direction::West,
+        Direction::West => Direction::North,
+        dir => dir,
+    }
+}
+
+// `rotate` for a block state written as a string; stairs shapes are relative to
+// their facing, so only the facing changes
+fn rotate_block(block: &str) -> String {
+    // `FACINGS` goes clockwise
+    for (i, facing) in FACINGS.iter().enumerate() {
+        let property = format!("facing={}", facing);
+        if block.contains(&property) {
+            return block.replace(&property, &format!("facing={}", FACINGS[(i + 1) % 4]));
+        }
+    }
+    block.to_owned()
+}
+
+#[test]
+fn moving_through_a_face_is_symmetric() {
+    let blocks = all_blocks();
+    for (a, b, dir) in iproduct!(&blocks, &blocks, Direction::all()) {
+        let a_state = parse_blockstate(a).unwrap();
+        let b_state = parse_blockstate(b).unwrap();
+        assert_eq!(
+            can_move(&a_state, &b_state, &dir),
+            can_move(&b_state, &a_state, &dir.opposite()),
+            "{} to {} towards {:?}",
+            a,
+            b,
+            dir
+        );
+    }
+}
+
+#[test]
+fn rotating_turns_the_result() {
+    let blocks = all_blocks();
+    for (a, b, dir) in iproduct!(&blocks, &blocks, Direction::all()) {
+        let turned_a = parse_blockstate(&rotate_block(a)).unwrap();
+        let turned_b = parse_blockstate(&rotate_block(b)).unwrap();
+        let a_state = parse_blockstate(a).unwrap();
+        let b_state = parse_blockstate(b).unwrap();
+        assert_eq!(
+            can_move(&a_state, &b_state, &dir),
+            can_move(&turned_a, &turned_b, &rotate(dir)),
+            "{} to {} towards {:?}",
+            a,
+            b,
+            dir
+        );
+        assert_eq!(
+            can_see(&a_state, &dir),
+            can_see(&turned_a, &rotate(dir)),
+            "{} towards {:?}",
+            a,
+            dir
+        );
+    }
+}
+
+#[test]
+fn stairs_fill_the_expected_octants() {
+    for (facing, half, shape) in iproduct!(FACINGS, HALVES, SHAPES) {
+        let block = stairs(facing, half, shape);
+        let corners = BlockShape::from(&parse_blockstate(&block).unwrap()).corners;
+
+        let filled = corners.iter().flatten().flatten().filter(|c| **c).count();
+        let expected = match shape {
+            "straight" => 6,
+            s if s.starts_with("inner_") => 7,
+            _ => 5,
+        };
+        assert_eq!(filled, expected, "{}", block);
+
+        // the whole half the stairs sit on is filled
+        let y = if half == "bottom" { 0 } else { 1 };
+        assert!(
+            corners.iter().all(|column| column[y].iter().all(|c| *c)),
+            "{}",
+            block
+        );
+    }
+}
+
+// vanilla's `StairBlock` makes each shape out of a slab plus some of the
+// quarters of the other half, picked from this table by `shape * 4 + facing`,
+// with facings in the order south, west, north, east; the quarters are
+// 1 = north-west, 2 = north-east, 4 = south-west, 8 = south-east
+const VANILLA_QUARTERS: [u8; 20] = [
+    12, 5, 3, 10, 14, 13, 7, 11, 13, 7, 11, 14, 8, 4, 1, 2, 4, 1, 2, 8,
+];
+
+#[test]
+fn stairs_match_vanilla() {
+    let facings = ["south", "west", "north", "east"];
+    for ((s, shape), (f, facing), half) in iproduct!(
+        SHAPES.iter().enumerate(),
+        facings.iter().enumerate(),
+        HALVES
+    ) {
+        let block = stairs(facing, half, shape);
+        let corners = BlockShape::from(&parse_blockstate(&block).unwrap()).corners;
+        let (slab_y, quarters_y) = if half == "bottom" { (0, 1) } else { (1, 0) };
+        let quarters = VANILLA_QUARTERS[s * 4 + f];
+
+        for (x, z) in iproduct!(0..2, 0..2) {
+            let quarter = match (x, z) {
+                (0, 0) => 1,
+                (1, 0) => 2,
+                (0, 1) => 4,
+                _ => 8,
+            };
+            assert!(corners[x][slab_y][z], "{} should have a full slab", block);
+            assert_eq!(
+                corners[x][quarters_y][z],
+                quarters & quarter != 0,
+                "{} at x={} z={}",
+                block,
+                x,
+                z
+            );
+        }
+    }
+}
+
+// a flood going octant by octant keeps the same blocks as the one going block
+// by block as long as these two hold: it can get from any empty octant of a
+// block to any other without leaving it, and whenever a block has an empty
+// octant on a face, the block across that face is either entered or seen
+
+#[test]
+fn empty_octants_of_a_block_are_connected() {
+    for block in all_blocks() {
+        let corners = BlockShape::from(&parse_blockstate(&block).unwrap()).corners;
+        let empty: Vec<(usize, usize, usize)> = iproduct!(0..2, 0..2, 0..2)
+            .filter(|&(x, y, z)| !corners[x][y][z])
+            .collect();
+        let Some(&first) = empty.first() else {
+            continue;
+        };
+
+        // octants sharing a face differ in exactly one coordinate
+        let mut reached = vec![first];
+        let mut i = 0;
+        while let Some(&(x, y, z)) = reached.get(i) {
+            for &next in &empty {
+                let (nx, ny, nz) = next;
+                let differences = (x != nx) as u8 + (y != ny) as u8 + (z != nz) as u8;
+                if differences == 1 && !reached.contains(&next) {
+                    reached.push(next);
+                }
+            }
+            i += 1;
+        }
+        assert_eq!(reached.len(), empty.len(), "{}", block);
+    }
+}
+
+#[test]
+fn an_open_face_enters_or_sees_the_next_block() {
+    let blocks = all_blocks();
+    for (a, b, dir) in iproduct!(&blocks, &blocks, Direction::all()) {
+        let a_state = parse_blockstate(a).unwrap();
+        let b_state = parse_blockstate(b).unwrap();
+        if can_see(&a_state, &dir) {
+            assert!(
+                can_move(&a_state, &b_state, &dir) || b_state.name != "minecraft:air",
+                "{} to {} towards {:?}",
+                a,
+                b,
+                dir
+            );
+        }
+    }
+}
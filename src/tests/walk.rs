@@ -0,0 +1,199 @@
+//  Small stone regions with rooms carved out of them, walked from a position
+//  inside with every face of the region closed, checking which blocks players
+//  get to see.
+
+use itertools::iproduct;
+use rustmatica::{util::Vec3, Region};
+use std::{borrow::Cow, ops::RangeInclusive};
+
+use super::optimize::{block, options};
+use crate::{optimize_region, progress, FloodMode, FloodOptions};
+
+const GOLD: &str = "minecraft:gold_block";
+
+// a stone region of the given size
+fn solid(x: i32, y: i32, z: i32) -> Region<'static> {
+    let mut region = Region::new(Cow::from("walk"), Vec3::new(0, 0, 0), Vec3::new(x, y, z));
+    for (pos, _) in region.clone().blocks() {
+        region.set_block(pos, block("minecraft:stone"));
+    }
+    region
+}
+
+fn fill(
+    region: &mut Region<'static>,
+    xs: RangeInclusive<i32>,
+    ys: RangeInclusive<i32>,
+    zs: RangeInclusive<i32>,
+    s: &str,
+) {
+    for (x, y, z) in iproduct!(xs, ys, zs) {
+        region.set_block(Vec3::new(x, y, z), block(s));
+    }
+}
+
+fn carve(
+    region: &mut Region<'static>,
+    xs: RangeInclusive<i32>,
+    ys: RangeInclusive<i32>,
+    zs: RangeInclusive<i32>,
+) {
+    fill(region, xs, ys, zs, "minecraft:air");
+}
+
+fn walk_options() -> FloodOptions {
+    FloodOptions {
+        open_faces: vec![],
+        ..options(FloodMode::Walk)
+    }
+}
+
+// walk `region` from `start`, returning how many blocks were hidden
+fn walk(region: &mut Region<'static>, start: Vec3) -> usize {
+    progress::hide();
+    optimize_region(region, &[start], &block("minecraft:air"), &walk_options()).unwrap()
+}
+
+fn hidden(region: &Region, pos: Vec3) -> bool {
+    region.get_block(pos).name == "minecraft:air"
+}
+
+#[test]
+fn solid_marker_is_stood_in_like_air() {
+    let mut region = solid(5, 4, 5);
+    carve(&mut region, 1..=3, 1..=2, 1..=3);
+    let marker = Vec3::new(2, 1, 2);
+    region.set_block(marker, block("minecraft:blue_concrete"));
+    walk(&mut region, marker);
+    for wall in [
+        Vec3::new(0, 1, 2),
+        Vec3::new(4, 2, 2),
+        Vec3::new(2, 0, 2),
+        Vec3::new(2, 3, 2),
+    ] {
+        assert!(!hidden(&region, wall), "wall at {:?}", wall);
+    }
+    // the corners don't touch the room
+    assert!(hidden(&region, Vec3::new(0, 0, 0)));
+}
+
+#[test]
+fn no_room_to_stand_is_an_error() {
+    progress::hide();
+    let mut region = solid(5, 3, 5);
+    carve(&mut region, 1..=3, 1..=1, 1..=3);
+    let marker = Vec3::new(2, 1, 2);
+    region.set_block(marker, block("minecraft:blue_concrete"));
+    let result = optimize_region(
+        &mut region,
+        &[marker],
+        &block("minecraft:air"),
+        &walk_options(),
+    );
+    assert!(result.is_err());
+    assert!(!hidden(&region, Vec3::new(0, 1, 2)));
+}
+
+// a room with a doorway to a second room that's either 2 blocks high or a
+// 1 block high crawlspace, with gold out of sight from the doorway
+fn rooms(crawlspace: bool) -> (Region<'static>, Vec3) {
+    let mut region = solid(9, 5, 7);
+    carve(&mut region, 1..=2, 1..=2, 1..=5);
+    carve(&mut region, 3..=3, 1..=2, 1..=1);
+    let height = if crawlspace { 1 } else { 2 };
+    carve(&mut region, 4..=7, 1..=height, 1..=5);
+    let gold = Vec3::new(6, 1, 4);
+    region.set_block(gold, block(GOLD));
+    (region, gold)
+}
+
+#[test]
+fn crawlspaces_are_not_entered() {
+    let (mut region, gold) = rooms(true);
+    walk(&mut region, Vec3::new(1, 1, 3));
+    assert!(hidden(&region, gold));
+
+    let (mut region, gold) = rooms(false);
+    walk(&mut region, Vec3::new(1, 1, 3));
+    assert!(!hidden(&region, gold));
+}
+
+// a corridor with a step up of `step` blocks, leading to a passage that
+// turns toward gold at its end
+fn steps(step: i32) -> (Region<'static>, Vec3) {
+    let mut region = solid(7, 7, 7);
+    // room to jump before the step
+    carve(&mut region, 1..=3, 1..=step + 2, 5..=5);
+    let floor = step + 1;
+    carve(&mut region, 4..=5, floor..=floor + 1, 5..=5);
+    carve(&mut region, 5..=5, floor..=floor + 1, 2..=4);
+    let gold = Vec3::new(5, floor, 1);
+    region.set_block(gold, block(GOLD));
+    (region, gold)
+}
+
+#[test]
+fn one_block_steps_are_climbed() {
+    let (mut region, gold) = steps(1);
+    walk(&mut region, Vec3::new(1, 1, 5));
+    assert!(!hidden(&region, gold));
+
+    let (mut region, gold) = steps(2);
+    walk(&mut region, Vec3::new(1, 1, 5));
+    assert!(hidden(&region, gold));
+}
+
+// a room with a shaft up to another room, with gold in a corner of the upper
+// room that can't be seen from the shaft
+fn shaft(climb: Option<&str>) -> (Region<'static>, Vec3) {
+    let mut region = solid(5, 9, 5);
+    carve(&mut region, 1..=3, 1..=2, 1..=3);
+    carve(&mut region, 2..=2, 3..=5, 2..=2);
+    carve(&mut region, 1..=3, 6..=7, 1..=3);
+    if let Some(climb) = climb {
+        fill(&mut region, 2..=2, 1..=5, 2..=2, climb);
+    }
+    let gold = Vec3::new(1, 6, 1);
+    region.set_block(gold, block(GOLD));
+    (region, gold)
+}
+
+#[test]
+fn ladders_and_scaffolding_are_climbed() {
+    for climb in ["minecraft:ladder[facing=north]", "minecraft:scaffolding"] {
+        let (mut region, gold) = shaft(Some(climb));
+        walk(&mut region, Vec3::new(1, 1, 1));
+        assert!(!hidden(&region, gold), "{}", climb);
+    }
+
+    let (mut region, gold) = shaft(None);
+    walk(&mut region, Vec3::new(1, 1, 1));
+    assert!(hidden(&region, gold));
+}
+
+#[test]
+fn walls_next_to_a_slab_floor_are_kept() {
+    let mut region = solid(5, 5, 5);
+    carve(&mut region, 1..=3, 2..=3, 1..=3);
+    fill(
+        &mut region,
+        1..=3,
+        1..=1,
+        1..=3,
+        "minecraft:oak_slab[type=bottom]",
+    );
+    walk(&mut region, Vec3::new(2, 2, 2));
+    // the upper half of these shows above the slabs
+    for wall in [
+        Vec3::new(0, 1, 2),
+        Vec3::new(4, 1, 2),
+        Vec3::new(2, 1, 0),
+        Vec3::new(2, 1, 4),
+    ] {
+        assert!(!hidden(&region, wall), "wall at {:?}", wall);
+    }
+    // the slabs themselves are stood on
+    assert!(!hidden(&region, Vec3::new(1, 1, 1)));
+    // and below them there's nothing to see
+    assert!(hidden(&region, Vec3::new(2, 0, 2)));
+}
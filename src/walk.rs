@@ -0,0 +1,311 @@
+//  Player reachability: instead of moving through any gap like light does, the
+//  flood follows the places where a player can actually stand:
+//
+//  - a player takes two blocks of clearance (feet and head) and needs something
+//    under their feet, unless they're holding on to a ladder, vines, etc.
+//
+//  - from a standing position, a player can walk to the sides, step up one
+//    block (slabs and stairs included, since they're not passable), walk off
+//    an edge and fall until something holds them, and climb up and down
+//    ladders and vines.
+//
+//  The starting positions are usually marker blocks that aren't really part of
+//  the build, so players can stand in them as if they were air.
+//
+//  Once we know where players can stand, they're assumed to look straight
+//  along the six directions from their feet and head, through anything they
+//  can see through, like the open half of slabs and stairs. Every block they
+//  see part of this way is kept, along with every block that shows through
+//  the cells they can see into. Players that can get out of the build can
+//  also look at it from anywhere around it, flying or from far away, so then
+//  all the air in the buffer around the region and the air open to the sky
+//  count as seen too, keeping roofs and overhangs. With a sight range, the
+//  blocks in the line of sight from their head are kept too, see `sight.rs`.
+//
+//  Shapes are looked up in the region's palette (see `palette.rs`).
+
+use anyhow::{bail, Result};
+use itertools::iproduct;
+use log::debug;
+use rustmatica::{util::Vec3, BlockState, Region};
+use std::{borrow::Cow, collections::VecDeque};
+
+use crate::{
+    is_just_outside, outside_shell,
+    palette::{self, FaceMasks, PackedBlocks, Palette, Shapes},
+    sight, Direction, PositionTracker,
+};
+
+const HORIZONTAL: [Direction; 4] = [
+    Direction::North,
+    Direction::South,
+    Direction::East,
+    Direction::West,
+];
+
+struct World<'a, 'b> {
+    region: &'b Region<'a>,
+    open_faces: &'b [Direction],
+    // anything at or below this height is solid ground
+    ground_y: i32,
+    palette: Palette,
+    // the starting positions, seen as air
+    markers: PositionTracker,
+    air: BlockState<'static>,
+}
+
+impl<'a, 'b> World<'a, 'b> {
+    fn new(
+        region: &'b Region<'a>,
+        blocks: &PackedBlocks,
+        open_faces: &'b [Direction],
+        starting_positions: &[Vec3],
+    ) -> Self {
+        // if the bottom face is open, players can walk around the build on the
+        // 1-block buffer under it, otherwise the build sits on the ground
+        let ground_y = if open_faces.contains(&Direction::Down) {
+            region.min_y() - 2
+        } else {
+            region.min_y() - 1
+        };
+        let mut markers = PositionTracker::new(region);
+        for pos in starting_positions.iter().filter(|pos| region.contains(pos)) {
+            markers.insert(pos);
+        }
+        Self {
+            region,
+            open_faces,
+            ground_y,
+            palette: Palette::layers(blocks, region, region.min_y(), region.max_y()),
+            markers,
+            air: BlockState {
+                name: Cow::from("minecraft:air"),
+                properties: None,
+            },
+        }
+    }
+
+    fn in_bounds(&self, pos: &Vec3) -> bool {
+        self.region.contains(pos) || is_just_outside(pos, self.region, self.open_faces)
+    }
+
+    fn block(&self, pos: &Vec3) -> &BlockState {
+        if self.region.contains(pos) && !self.markers.contains(pos) {
+            self.region.get_block(*pos)
+        } else {
+            &self.air
+        }
+    }
+
+    // the palette index of the block at `pos`
+    fn index(&self, pos: &Vec3) -> u32 {
+        if self.markers.contains(pos) {
+            palette::AIR
+        } else {
+            self.palette.get(self.region, pos)
+        }
+    }
+
+    fn masks(&self, pos: &Vec3) -> &FaceMasks {
+        self.palette.masks(self.index(pos))
+    }
+
+    fn passable(&self, pos: &Vec3) -> bool {
+        if pos.y <= self.ground_y {
+            return false;
+        }
+        self.palette.is_empty(self.index(pos))
+    }
+
+    // whether a player looking along `dir` can see through the cell at `pos`,
+    // in one side and out the other
+    fn can_see_through(&self, pos: &Vec3, dir: Direction) -> bool {
+        let masks = self.masks(pos);
+        masks.can_move(masks, dir)
+    }
+
+    fn climbable(&self, pos: &Vec3) -> bool {
+        let name = &self.block(pos).name;
+        name.ends_with("ladder")
+            || name.ends_with("vine")
+            || name.ends_with("vines")
+            || name.ends_with("scaffolding")
+            || name == "minecraft:water"
+    }
+
+    fn can_stand(&self, pos: &Vec3) -> bool {
+        self.in_bounds(pos)
+            && self.passable(pos)
+            && self.passable(&(*pos + Direction::Up))
+            && (!self.passable(&(*pos + Direction::Down)) || self.climbable(pos))
+    }
+
+    // where a player that steps into `pos` ends up after falling
+    fn land(&self, pos: Vec3) -> Option<Vec3> {
+        let mut pos = pos;
+        while self.in_bounds(&pos) && self.passable(&pos) {
+            if self.can_stand(&pos) {
+                return Some(pos);
+            }
+            pos = pos + Direction::Down;
+        }
+        None
+    }
+
+    // the standing positions a player can get to from `pos` in one move
+    fn moves(&self, pos: Vec3) -> Vec<Vec3> {
+        let mut moves = vec![];
+        let up = pos + Direction::Up;
+
+        for dir in HORIZONTAL {
+            let next_pos = pos + dir;
+            if self.can_stand(&next_pos) {
+                moves.push(next_pos);
+            } else if self.passable(&next_pos) && self.passable(&(next_pos + Direction::Up)) {
+                // walking off an edge
+                moves.extend(self.land(next_pos));
+            } else if self.passable(&(up + Direction::Up))
+                && self.can_stand(&(next_pos + Direction::Up))
+            {
+                // stepping up, there has to be room over the player's head to jump
+                moves.push(next_pos + Direction::Up);
+            }
+        }
+
+        if (self.climbable(&pos) || self.climbable(&up))
+            && self.in_bounds(&up)
+            && self.passable(&up)
+            && self.passable(&(up + Direction::Up))
+        {
+            moves.push(up);
+        }
+
+        let down = pos + Direction::Down;
+        if self.climbable(&down) && self.in_bounds(&down) && self.passable(&down) {
+            moves.push(down);
+        }
+
+        moves
+    }
+}
+
+/// The blocks players can see from where they can stand, and the cells they can
+/// see into
+pub(crate) fn reachable_blocks<'a>(
+    region: &'a Region<'a>,
+    blocks: &PackedBlocks,
+    starting_positions: &[Vec3],
+    open_faces: &[Direction],
+    sight_range: usize,
+) -> Result<(PositionTracker, PositionTracker)> {
+    let world = World::new(region, blocks, open_faces, starting_positions);
+
+    let mut standing = PositionTracker::new(region);
+    let mut standing_positions = vec![];
+    let mut q = VecDeque::new();
+
+    for starting_pos in starting_positions {
+        let Some(pos) = world.land(*starting_pos) else {
+            continue;
+        };
+        if !standing.contains(&pos) {
+            standing.insert(&pos);
+            standing_positions.push(pos);
+            q.push_back(pos);
+        }
+    }
+
+    while let Some(pos) = q.pop_front() {
+        for next_pos in world.moves(pos) {
+            if standing.contains(&next_pos) {
+                continue;
+            }
+            standing.insert(&next_pos);
+            standing_positions.push(next_pos);
+            q.push_back(next_pos);
+        }
+    }
+    debug!(
+        "Players can stand on {} positions",
+        standing_positions.len()
+    );
+    // with nowhere to stand, nothing would be seen and the whole build hidden
+    if standing_positions.is_empty() {
+        bail!(
+            "There's no room to stand at any starting position in region {}",
+            region.name
+        );
+    }
+
+    // the cells players can see into from their feet and head
+    let mut seen = PositionTracker::new(region);
+    for pos in standing_positions.iter().copied() {
+        for eye in [pos, pos + Direction::Up] {
+            seen.insert(&eye);
+            for dir in Direction::all() {
+                let mut current = eye + dir;
+                while world.in_bounds(&current) {
+                    seen.insert(&current);
+                    if !world.can_see_through(&current, dir) {
+                        break;
+                    }
+                    current = current + dir;
+                }
+            }
+        }
+    }
+
+    // and from anywhere around the build, if they can get out of it
+    if standing_positions.iter().any(|pos| !region.contains(pos)) {
+        for pos in outside_shell(region, open_faces) {
+            if world.passable(&pos) {
+                seen.insert(&pos);
+            }
+        }
+        if open_faces.contains(&Direction::Up) {
+            for (x, z) in iproduct!(
+                region.min_x()..=region.max_x(),
+                region.min_z()..=region.max_z()
+            ) {
+                let mut pos = Vec3::new(x, region.max_y(), z);
+                while region.contains(&pos) && world.passable(&pos) {
+                    seen.insert(&pos);
+                    pos = pos + Direction::Down;
+                }
+            }
+        }
+    }
+
+    let mut reachable_blocks = PositionTracker::new(region);
+    for (pos, blockstate) in region.blocks() {
+        if blockstate.name == "minecraft:air" {
+            continue;
+        }
+        // part of the block is in sight, or it shows through a cell in sight
+        let visible = seen.contains(&pos)
+            || Direction::all().into_iter().any(|dir| {
+                let from = pos + dir;
+                world.in_bounds(&from)
+                    && seen.contains(&from)
+                    && world.masks(&from).can_see(dir.opposite())
+            });
+        if visible {
+            reachable_blocks.insert(&pos);
+        }
+    }
+
+    if sight_range > 0 {
+        let eyes: Vec<Vec3> = standing_positions
+            .iter()
+            .map(|pos| *pos + Direction::Up)
+            .collect();
+        sight::mark_visible(
+            region,
+            &world.palette,
+            &eyes,
+            sight_range,
+            &mut reachable_blocks,
+        );
+    }
+    Ok((reachable_blocks, seen))
+}
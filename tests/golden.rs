@@ -0,0 +1,91 @@
+//  End-to-end runs of the optimatica binary over the schematics in
+//  `tests/fixtures`, comparing the material lists of what it writes with the
+//  golden `.txt` files next to them.
+
+use std::{fs, path::PathBuf, process::Command};
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+fn output(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name)
+}
+
+fn optimatica(args: &[&str]) -> String {
+    let result = Command::new(env!("CARGO_BIN_EXE_optimatica"))
+        .arg("--quiet")
+        .args(args)
+        .output()
+        .expect("optimatica should run");
+    assert!(
+        result.status.success(),
+        "optimatica {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&result.stderr)
+    );
+    String::from_utf8(result.stdout).expect("output should be UTF-8")
+}
+
+fn materials(path: &PathBuf) -> String {
+    optimatica(&["materials", path.to_str().unwrap()])
+}
+
+fn golden(name: &str) -> String {
+    fs::read_to_string(fixture(name)).expect("golden file should exist")
+}
+
+#[test]
+fn materials_of_sealed_box() {
+    assert_eq!(
+        materials(&fixture("sealed_box.litematic")),
+        golden("sealed_box.materials.txt")
+    );
+}
+
+#[test]
+fn optimize_sealed_box() {
+    let out = output("sealed_box.optimized.litematic");
+    optimatica(&[
+        "optimize",
+        fixture("sealed_box.litematic").to_str().unwrap(),
+        out.to_str().unwrap(),
+        "--from-outside",
+    ]);
+    assert_eq!(materials(&out), golden("sealed_box.optimized.txt"));
+}
+
+#[test]
+fn optimize_leaky_box() {
+    assert_eq!(
+        materials(&fixture("leaky_box.litematic")),
+        golden("leaky_box.materials.txt")
+    );
+
+    let out = output("leaky_box.optimized.litematic");
+    optimatica(&[
+        "optimize",
+        fixture("leaky_box.litematic").to_str().unwrap(),
+        out.to_str().unwrap(),
+        "--from-outside",
+    ]);
+    assert_eq!(materials(&out), golden("leaky_box.optimized.txt"));
+}
+
+#[test]
+fn replace_markers() {
+    assert_eq!(
+        materials(&fixture("markers.litematic")),
+        golden("markers.materials.txt")
+    );
+
+    let out = output("markers.replaced.litematic");
+    optimatica(&[
+        "replace",
+        fixture("markers.litematic").to_str().unwrap(),
+        out.to_str().unwrap(),
+    ]);
+    assert_eq!(materials(&out), golden("markers.replaced.txt"));
+}